@@ -0,0 +1,132 @@
+use std::fmt::{Display, Formatter};
+use num::{BigInt, CheckedAdd, CheckedMul, One, Zero};
+use crate::options::Options;
+
+/// The answer to a puzzle, which may be an integer of any size or some text.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Answer {
+    Int(i128),
+    Big(BigInt),
+    Text(String)
+}
+
+impl Display for Answer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Answer::Int(n) => write!(f, "{n}"),
+            Answer::Big(n) => write!(f, "{n}"),
+            Answer::Text(s) => write!(f, "{s}")
+        }
+    }
+}
+
+macro_rules! answer_from_int {
+    ($($t:ty),*) => {
+        $(
+            impl From<$t> for Answer {
+                fn from(n: $t) -> Self {
+                    Answer::Int(n as i128)
+                }
+            }
+        )*
+    };
+}
+
+answer_from_int!(i32, u32, i64, u64, usize, i128);
+
+impl From<u128> for Answer {
+    fn from(n: u128) -> Self {
+        match i128::try_from(n) {
+            Ok(n) => Answer::Int(n),
+            Err(_) => Answer::Big(BigInt::from(n))
+        }
+    }
+}
+
+impl From<BigInt> for Answer {
+    fn from(n: BigInt) -> Self {
+        Answer::Big(n)
+    }
+}
+
+impl From<String> for Answer {
+    fn from(s: String) -> Self {
+        Answer::Text(s)
+    }
+}
+
+/// Whether the `big` option was given, asking solvers to use arbitrary-precision integers.
+pub(crate) fn big_mode(opts: &Options) -> bool {
+    opts.flag("big")
+}
+
+/// Sum the values, panicking if the sum overflows `T`. If the `big` option is set, the values are
+/// summed as big integers instead.
+pub(crate) fn sum<T, I>(values: I, opts: &Options) -> Answer
+    where I: IntoIterator<Item = T>, T: CheckedAdd + Zero + Into<BigInt> + Into<Answer> {
+    if big_mode(opts) {
+        return values.into_iter().map(Into::<BigInt>::into).sum::<BigInt>().into()
+    }
+    values.into_iter()
+        .try_fold(T::zero(), |acc, v| acc.checked_add(&v))
+        .expect("Sum overflowed; try the `big` option.")
+        .into()
+}
+
+/// Multiply the values, panicking if the product overflows `T`. If the `big` option is set, the
+/// values are multiplied as big integers instead.
+pub(crate) fn product<T, I>(values: I, opts: &Options) -> Answer
+    where I: IntoIterator<Item = T>, T: CheckedMul + One + Into<BigInt> + Into<Answer> {
+    if big_mode(opts) {
+        return values.into_iter().map(Into::<BigInt>::into).product::<BigInt>().into()
+    }
+    values.into_iter()
+        .try_fold(T::one(), |acc, v| acc.checked_mul(&v))
+        .expect("Product overflowed; try the `big` option.")
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn big() -> Options {
+        Options::from_args([String::from("big")].into_iter())
+    }
+
+    #[test]
+    fn converts_values() {
+        assert_eq!(Answer::from(-5), Answer::Int(-5));
+        assert_eq!(Answer::from(u128::MAX >> 1), Answer::Int(i128::MAX));
+        assert_eq!(Answer::from(u128::MAX), Answer::Big(BigInt::from(u128::MAX)));
+        assert_eq!(Answer::from(String::from("a\nb")).to_string(), "a\nb");
+        assert_eq!(Answer::from(u128::MAX).to_string(), u128::MAX.to_string());
+    }
+
+    #[test]
+    fn sums_and_multiplies() {
+        let opts = Options::default();
+        assert_eq!(sum([1u64, 2, 3], &opts), Answer::Int(6));
+        assert_eq!(product([2u64, 3, 4], &opts), Answer::Int(24));
+        assert_eq!(sum(Vec::<u64>::new(), &opts), Answer::Int(0));
+        assert_eq!(product(Vec::<u64>::new(), &opts), Answer::Int(1));
+
+        // In big mode the values are combined as big integers, so can't overflow.
+        let expected = BigInt::from(u64::MAX) * 2;
+        assert_eq!(sum([u64::MAX, u64::MAX], &big()), Answer::Big(expected));
+        let expected = BigInt::from(u64::MAX) * BigInt::from(u64::MAX);
+        assert_eq!(product([u64::MAX, u64::MAX], &big()), Answer::Big(expected));
+    }
+
+    #[test]
+    #[should_panic(expected = "Sum overflowed; try the `big` option.")]
+    fn panics_when_a_sum_overflows() {
+        sum([u64::MAX, 1], &Options::default());
+    }
+
+    #[test]
+    #[should_panic(expected = "Product overflowed; try the `big` option.")]
+    fn panics_when_a_product_overflows() {
+        product([u32::MAX, 2], &Options::default());
+    }
+}
@@ -229,3 +229,19 @@ impl<'a, T> Iterator for GridPositionIterator<'a, T> {
     }
 }
 
+
+/// A small deterministic generator of pseudo-random numbers, for tests that compare an algorithm
+/// against brute force on many generated inputs.
+#[cfg(test)]
+pub(crate) struct Lcg(pub(crate) u64);
+
+#[cfg(test)]
+impl Lcg {
+
+    /// Return a number from 0 up to but not including `n`, which must be positive.
+    pub(crate) fn below<T>(&mut self, n: T) -> T where T: num::PrimInt {
+        self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        let n = n.to_u64().expect("Bound should be positive.");
+        T::from((self.0 >> 33) % n).expect("Number below the bound should fit its type.")
+    }
+}
@@ -0,0 +1,261 @@
+use num::Integer;
+
+/// The shape of the sequence of states produced by repeatedly applying a step function to a start
+/// state: `tail` steps before the sequence first enters its cycle, then a cycle of `length`
+/// states repeating forever.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Cycle {
+    pub(crate) tail: u64,
+    pub(crate) length: u64
+}
+
+/// Find the tail and cycle length of the sequence starting at `start` using Brent's algorithm.
+/// The state space reachable from `start` must be finite.
+pub(crate) fn brent<S, F>(start: &S, step: F) -> Cycle where S: Clone + Eq, F: Fn(&S) -> S {
+    // Find the cycle length by moving the hare forward until it meets the tortoise, teleporting
+    // the tortoise to the hare at each power of two.
+    let mut power = 1;
+    let mut length = 1;
+    let mut tortoise = start.clone();
+    let mut hare = step(start);
+    while tortoise != hare {
+        if power == length {
+            tortoise = hare.clone();
+            power *= 2;
+            length = 0;
+        }
+        hare = step(&hare);
+        length += 1;
+    }
+
+    // Find the tail length by starting the hare one cycle length ahead of the tortoise and
+    // moving them together until they meet at the start of the cycle.
+    tortoise = start.clone();
+    hare = start.clone();
+    for _ in 0..length {
+        hare = step(&hare);
+    }
+    let mut tail = 0;
+    while tortoise != hare {
+        tortoise = step(&tortoise);
+        hare = step(&hare);
+        tail += 1;
+    }
+    Cycle { tail, length }
+}
+
+/// The steps at which a cyclic sequence of states satisfies some condition.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct HitPattern {
+    pub(crate) cycle: Cycle,
+    /// Sorted steps, before the cycle is entered, at which the condition holds.
+    pub(crate) tail_hits: Vec<u64>,
+    /// Sorted steps, during the first pass through the cycle, at which the condition holds. Every
+    /// later pass repeats these at intervals of the cycle length.
+    pub(crate) cycle_hits: Vec<u64>
+}
+
+impl HitPattern {
+
+    /// Whether the condition holds after `t` steps.
+    pub(crate) fn is_hit(&self, t: u64) -> bool {
+        if t < self.cycle.tail {
+            self.tail_hits.binary_search(&t).is_ok()
+        } else {
+            let offset = (t - self.cycle.tail) % self.cycle.length;
+            self.cycle_hits.binary_search(&(self.cycle.tail + offset)).is_ok()
+        }
+    }
+
+    /// The first step at which the condition holds, if it ever does.
+    pub(crate) fn first_hit(&self) -> Option<u64> {
+        self.tail_hits.first().or(self.cycle_hits.first()).copied()
+    }
+
+    /// All steps before `limit` at which the condition holds, in order.
+    fn hits_below(&self, limit: u64) -> Vec<u64> {
+        let mut hits: Vec<u64> = self.tail_hits.iter()
+            .copied()
+            .take_while(|t| *t < limit)
+            .collect();
+        for c in &self.cycle_hits {
+            hits.extend((*c..limit).step_by(self.cycle.length as usize));
+        }
+        hits.sort();
+        hits
+    }
+}
+
+/// Detect the cycle in the sequence starting at `start` and record the steps at which `is_hit`
+/// holds.
+pub(crate) fn analyse<S, F, P>(start: &S, step: F, is_hit: P) -> HitPattern
+    where S: Clone + Eq, F: Fn(&S) -> S, P: Fn(&S) -> bool {
+    let cycle = brent(start, &step);
+    let mut tail_hits: Vec<u64> = vec!();
+    let mut cycle_hits: Vec<u64> = vec!();
+    let mut state = start.clone();
+    for t in 0..(cycle.tail + cycle.length) {
+        if is_hit(&state) {
+            if t < cycle.tail {
+                tail_hits.push(t);
+            } else {
+                cycle_hits.push(t);
+            }
+        }
+        state = step(&state);
+    }
+    HitPattern { cycle, tail_hits, cycle_hits }
+}
+
+/// Combine two congruences x ≡ r1 (mod m1) and x ≡ r2 (mod m2), with each residue already
+/// reduced modulo its modulus, into a single congruence x ≡ r (mod lcm(m1, m2)), returned as
+/// (r, lcm). The moduli need not be coprime. Returns None if no x satisfies both, or an error if
+/// the combined modulus overflows.
+fn combine((r1, m1): (i128, i128), (r2, m2): (i128, i128))
+    -> Result<Option<(i128, i128)>, String> {
+    let overflow = || String::from("Combined cycle length overflowed.");
+    let egcd = m1.extended_gcd(&m2);
+    let g = egcd.gcd;
+    if (r2 - r1) % g != 0 {
+        return Ok(None)
+    }
+    let m2_g = m2 / g;
+    // egcd.x is the inverse of m1 / g modulo m2 / g.
+    let k = ((r2 - r1) / g % m2_g).checked_mul(egcd.x % m2_g).ok_or_else(overflow)?
+        .rem_euclid(m2_g);
+    let lcm = (m1 / g).checked_mul(m2).ok_or_else(overflow)?;
+    let r = m1.checked_mul(k).and_then(|x| x.checked_add(r1)).ok_or_else(overflow)?;
+    Ok(Some((r.rem_euclid(lcm), lcm)))
+}
+
+/// Solve a system of congruences, each given as (residue, modulus), using the generalised Chinese
+/// Remainder Theorem. Returns (r, m) such that the solutions are exactly the x with x ≡ r (mod m),
+/// None if there are no solutions, or an error if the combined modulus overflows.
+#[allow(dead_code)]
+pub(crate) fn crt(congruences: &[(i128, i128)]) -> Result<Option<(i128, i128)>, String> {
+    let mut solution = (0, 1);
+    for (r, m) in congruences {
+        match combine(solution, (r.rem_euclid(*m), *m))? {
+            Some(s) => solution = s,
+            None => return Ok(None)
+        }
+    }
+    Ok(Some(solution))
+}
+
+/// Return the smallest x >= `limit` such that x ≡ r (mod m), or None if it overflows.
+fn first_at_least(r: i128, m: i128, limit: i128) -> Option<i128> {
+    if r >= limit {
+        Some(r)
+    } else {
+        ((limit - r + m - 1) / m).checked_mul(m)?.checked_add(r)
+    }
+}
+
+/// Return the first step at which every pattern's condition holds at once, if there is one, or an
+/// error if the step is too large to calculate.
+pub(crate) fn first_common_hit(patterns: &[HitPattern]) -> Result<Option<u128>, String> {
+    let Some(max_tail) = patterns.iter().map(|p| p.cycle.tail).max() else {
+        return Ok(None)
+    };
+
+    // Until every sequence has entered its cycle we can just check each hit of the first one.
+    for t in patterns[0].hits_below(max_tail) {
+        if patterns.iter().all(|p| p.is_hit(t)) {
+            return Ok(Some(t as u128))
+        }
+    }
+
+    // After that, each sequence is hit exactly when the step is congruent to one of its cycle
+    // hits modulo its cycle length, so solve for every combination of cycle hits. Combinations
+    // are built up one pattern at a time so that contradictory ones are discarded early.
+    let mut solutions: Vec<(i128, i128)> = vec!((0, 1));
+    for p in patterns {
+        let length = p.cycle.length as i128;
+        let mut next: Vec<(i128, i128)> = vec!();
+        for s in &solutions {
+            for c in &p.cycle_hits {
+                next.extend(combine(*s, (*c as i128 % length, length))?);
+            }
+        }
+        solutions = next;
+    }
+    solutions.iter()
+        .map(|(r, m)| first_at_least(*r, *m, max_tail as i128)
+            .map(|t| t as u128)
+            .ok_or_else(|| String::from("First common hit overflowed.")))
+        .collect::<Result<Vec<u128>, String>>()
+        .map(|hits| hits.into_iter().min())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::Lcg;
+    use super::*;
+
+    #[test]
+    fn finds_tails_and_cycles() {
+        // 0 -> 1 -> 2 -> 3 -> 4 -> 5 -> 2
+        let step = |s: &u32| if *s == 5 { 2 } else { s + 1 };
+        assert_eq!(brent(&0, step), Cycle { tail: 2, length: 4 });
+        assert_eq!(brent(&3, step), Cycle { tail: 0, length: 4 });
+        assert_eq!(brent(&7, |_: &u32| 7), Cycle { tail: 0, length: 1 });
+
+        let pattern = analyse(&0, step, |s| s % 2 == 1);
+        assert_eq!(pattern.tail_hits, vec!(1));
+        assert_eq!(pattern.cycle_hits, vec!(3, 5));
+        assert!(pattern.is_hit(7) && pattern.is_hit(9) && !pattern.is_hit(8));
+        assert_eq!(pattern.hits_below(10), vec!(1, 3, 5, 7, 9));
+        assert_eq!(pattern.first_hit(), Some(1));
+    }
+
+    #[test]
+    fn combines_congruences() {
+        // x ≡ 2 (mod 6) and x ≡ 4 (mod 10) share the factor 2, and are solved by 14 mod 30.
+        assert_eq!(combine((2, 6), (4, 10)), Ok(Some((14, 30))));
+        assert_eq!(combine((1, 6), (4, 10)), Ok(None));
+        assert_eq!(combine((3, 4), (3, 4)), Ok(Some((3, 4))));
+        assert!(combine((0, 1 << 100), (1, (1 << 100) - 1)).is_err());
+
+        assert_eq!(crt(&[(2, 3), (3, 5), (2, 7)]), Ok(Some((23, 105))));
+        assert_eq!(crt(&[(-1, 4), (1, 6)]), Ok(Some((7, 12))));
+        assert_eq!(crt(&[(0, 4), (1, 6)]), Ok(None));
+        assert_eq!(crt(&[]), Ok(Some((0, 1))));
+    }
+
+    /// Simulate every sequence together to find the first step before `limit` at which all of them
+    /// are hit.
+    fn brute_first_common_hit(next: &[usize], starts: &[usize], hits: &[bool], limit: u64)
+        -> Option<u128> {
+        let mut states: Vec<usize> = starts.to_vec();
+        for t in 0..limit {
+            if states.iter().all(|s| hits[*s]) {
+                return Some(t as u128)
+            }
+            states.iter_mut().for_each(|s| *s = next[*s]);
+        }
+        None
+    }
+
+    #[test]
+    fn finds_first_common_hits_like_brute_force() {
+        let mut rng = Lcg(1);
+        for _ in 0..2000 {
+            // A random function on a few states, with some of them marked as hits.
+            let n = 1 + rng.below(12);
+            let next: Vec<usize> = (0..n).map(|_| rng.below(n)).collect();
+            let hits: Vec<bool> = (0..n).map(|_| rng.below(3) == 0).collect();
+            let starts: Vec<usize> = (0..1 + rng.below(3)).map(|_| rng.below(n)).collect();
+            let patterns: Vec<HitPattern> = starts.iter()
+                .map(|s| analyse(s, |s| next[*s], |s| hits[*s]))
+                .collect();
+            // After the longest tail the states repeat with the lcm of the cycle lengths, so any
+            // common hit comes before the end of the first such repeat.
+            let limit = patterns.iter().map(|p| p.cycle.tail).max().unwrap()
+                + patterns.iter().fold(1, |acc, p| acc.lcm(&p.cycle.length));
+            assert_eq!(first_common_hit(&patterns),
+                       Ok(brute_first_common_hit(&next, &starts, &hits, limit)),
+                       "next {next:?}, hits {hits:?}, starts {starts:?}");
+        }
+    }
+}
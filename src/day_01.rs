@@ -1,81 +1,125 @@
-use std::collections::HashMap;
+use crate::matcher::Matcher;
+use crate::answer::{self, Answer};
+use crate::options::Options;
 
-fn new_digit_word_map<'a>() -> HashMap<&'a str, char> {
-    let mut digits = HashMap::new();
-    digits.insert("one", '1');
-    digits.insert("two", '2');
-    digits.insert("three",'3');
-    digits.insert("four", '4');
-    digits.insert("five", '5');
-    digits.insert("six", '6');
-    digits.insert("seven", '7');
-    digits.insert("eight", '8');
-    digits.insert("nine", '9');
-    digits
-}
+/// The digit words recognised in part 2 if no `words` option is given.
+const DEFAULT_WORDS: &str = "one:1,two:2,three:3,four:4,five:5,six:6,seven:7,eight:8,nine:9";
 
-fn words_to_digits(s: &str) -> String {
-    let chars: Vec<char> = s.chars().collect();
-    let digit_word_map = new_digit_word_map();
-    let mut new_s = String::new();
-    let mut next_push_unmatched = 0;  // the next position at (or after) which we will push a
-                                      // character, which doesn't match a "number word", to the new
-                                      // string.
-    let mut word_size: usize;  // the size of a number word
-    let mut matched: bool;  // whether a number word match has been found
-    let mut to_test: String;  // the slice of text we are to test against each number word
-    for (i, c) in chars.iter().enumerate() {
-        matched = false;
-        for (w, d) in digit_word_map.iter() {
-            // for each number word, get the length of that word, look ahead that many
-            // characters and compare to the number word
-            word_size = w.len();
-            if i + word_size > chars.len() {
-                // looking ahead would take us out of bounds (so word can't match)
-                continue;
-            }
-            to_test = chars[i..i+word_size].iter().collect();
-            if to_test == *w {
-                // found a match
-                new_s.push(*d);
-                matched = true;
-                next_push_unmatched = i + word_size;
-                break;
+/// The code point of the zero of each set of Unicode 15 decimal digits (general category Nd). Each
+/// set is ten consecutive code points running from zero to nine. The first is ASCII.
+const UNICODE_ZEROS: [u32; 68] = [
+    0x0030, 0x0660, 0x06F0, 0x07C0, 0x0966, 0x09E6, 0x0A66, 0x0AE6, 0x0B66, 0x0BE6, 0x0C66,
+    0x0CE6, 0x0D66, 0x0DE6, 0x0E50, 0x0ED0, 0x0F20, 0x1040, 0x1090, 0x17E0, 0x1810, 0x1946,
+    0x19D0, 0x1A80, 0x1A90, 0x1B50, 0x1BB0, 0x1C40, 0x1C50, 0xA620, 0xA8D0, 0xA900, 0xA9D0,
+    0xA9F0, 0xAA50, 0xABF0, 0xFF10, 0x104A0, 0x10D30, 0x11066, 0x110F0, 0x11136, 0x111D0,
+    0x112F0, 0x11450, 0x114D0, 0x11650, 0x116C0, 0x11730, 0x118E0, 0x11950, 0x11C50, 0x11D50,
+    0x11DA0, 0x11F50, 0x16A60, 0x16AC0, 0x16B50, 0x1D7CE, 0x1D7D8, 0x1D7E2, 0x1D7EC, 0x1D7F6,
+    0x1E140, 0x1E2F0, 0x1E4F0, 0x1E950, 0x1FBF0
+];
+
+/// Parse a list of digit words of the form `one:1,two:2`.
+fn parse_words(s: &str) -> Result<Vec<(String, u32)>, String> {
+    s.split(',')
+        .map(|entry| {
+            let (word, digit_s) = entry.split_once(':')
+                .ok_or(format!("Digit word `{entry}` should be in format `<word>:<digit>`."))?;
+            match digit_s.parse::<u32>() {
+                Ok(digit) if digit < 10 => Ok((String::from(word), digit)),
+                _ => Err(format!("`{digit_s}` is not a single digit."))
             }
-        }
-        if (!matched) && (i >= next_push_unmatched) {
-            // didn't match any number word, so just push the character directly
-            new_s.push(*c);
-        }
-    }
-    new_s
+        })
+        .collect()
 }
 
-fn first_digit<I>(chars: I) -> char where I: Iterator<Item = char> {
-    // Find the first numeric character in a sequence of characters
-    for c in chars {
-        if c.is_numeric() {
-            return c
+/// Build a matcher for numeric digits and the given digit words. Numeric digits are the ASCII
+/// digits, plus every other Unicode decimal digit if `unicode` is true.
+fn digit_matcher(words: &[(String, u32)], unicode: bool) -> Matcher<u32> {
+    let zeros = if unicode { &UNICODE_ZEROS[..] } else { &UNICODE_ZEROS[..1] };
+    let numeric: Vec<(String, u32)> = zeros.iter()
+        .flat_map(|zero| (0..10).map(move |d| (zero + d, d)))
+        .map(|(c, d)| (char::from_u32(c).expect("Invalid digit code point.").to_string(), d))
+        .collect();
+    Matcher::new(numeric.iter().chain(words).map(|(s, d)| (s.as_str(), *d)))
+}
+
+/// Find the calibration value of every line: the first digit found in the line followed by the
+/// last. Returns an error listing the line numbers of any lines without digits. If `audit` is
+/// true, prints each line's calibration value.
+fn calibrate(s: &str, matcher: &Matcher<u32>, audit: bool) -> Result<Vec<u32>, String> {
+    let mut values: Vec<u32> = vec!();
+    let mut missing: Vec<String> = vec!();
+    for (i, line) in s.lines().enumerate() {
+        match (matcher.first(line), matcher.last(line)) {
+            (Some(first), Some(last)) => {
+                let value = (first * 10) + last;
+                if audit {
+                    println!("Line {}: {value} from `{line}`", i + 1);
+                }
+                values.push(value);
+            },
+            _ => missing.push((i + 1).to_string())
         }
     }
-    panic!("No digits found.")
+    if !missing.is_empty() {
+        return Err(format!("No digits found on line(s) {}.", missing.join(", ")))
+    }
+    Ok(values)
 }
 
-pub(crate) fn part_1(s: &str) -> String {
-    let mut cal: Vec<u32> = vec!();
-    for line in s.lines() {
-        let chars = line.chars();
-        let first = first_digit(chars.clone()).to_digit(10)
-            .expect("First digit character cannot be converted to number.");
-        let last = first_digit(chars.rev()).to_digit(10)
-            .expect("Last digit character cannot be converted to number.");
-        cal.push((first * 10) + last)
-    }
-    let total: u32 = cal.iter().sum();
-    total.to_string()
+/// Sum the calibration values with the given digit words. Only ASCII digits are recognised unless
+/// the `unicode` option is set, and each line's value is printed if the `audit` option is set.
+fn solve(s: &str, words: &[(String, u32)], opts: &Options) -> Answer {
+    let matcher = digit_matcher(words, opts.flag("unicode"));
+    let values = calibrate(s, &matcher, opts.flag("audit")).unwrap_or_else(|e| panic!("{e}"));
+    answer::sum(values, opts)
+}
+
+pub(crate) fn part_1(s: &str, opts: &Options) -> Answer {
+    solve(s, &[], opts)
 }
 
-pub(crate) fn part_2(s: &str) -> String {
-    let fixed = words_to_digits(s);
-    part_1(&fixed)
-}
\ No newline at end of file
+/// As part 1, but also recognising the digit words given by the `words` option, in the form
+/// `one:1,two:2` (by default the English words for 1 to 9).
+pub(crate) fn part_2(s: &str, opts: &Options) -> Answer {
+    let words = parse_words(opts.get("words").unwrap_or(DEFAULT_WORDS))
+        .expect("Could not parse digit words.");
+    solve(s, &words, opts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(args: &[&str]) -> Options {
+        Options::from_args(args.iter().map(|a| String::from(*a)))
+    }
+
+    #[test]
+    fn reads_overlapping_digit_words() {
+        let input = "eightwo\noneight\ntwone\n";
+        assert_eq!(part_2(input, &Options::default()), Answer::from(82 + 18 + 21));
+        assert_eq!(part_2("zeroone1\n", &options(&["words=zero:0,one:1"])), Answer::from(1));
+        assert!(parse_words("zero:10").is_err());
+        assert!(parse_words("zero").is_err());
+    }
+
+    #[test]
+    fn recognises_unicode_digits_only_when_asked() {
+        let ascii = digit_matcher(&[], false);
+        assert_eq!(calibrate("a٣1b\n7\n", &ascii, false), Ok(vec!(11, 77)));
+        assert_eq!(calibrate("٣\n", &ascii, false),
+                   Err(String::from("No digits found on line(s) 1.")));
+
+        let unicode = digit_matcher(&[], true);
+        assert_eq!(calibrate("a٣1b\n𝟠x९\n", &unicode, false), Ok(vec!(31, 89)));
+        let digits = UNICODE_ZEROS.iter().flat_map(|zero| (0..10).map(move |d| zero + d));
+        assert!(digits.map(char::from_u32).all(|c| c.is_some_and(char::is_numeric)));
+    }
+
+    #[test]
+    fn lists_lines_without_digits() {
+        let matcher = digit_matcher(&parse_words(DEFAULT_WORDS).unwrap(), false);
+        assert_eq!(calibrate("abc\nxyz\n1two\nnone\n", &matcher, false),
+                   Err(String::from("No digits found on line(s) 1, 2.")));
+    }
+}
@@ -1,76 +1,174 @@
 use std::collections::HashMap;
+use std::str::FromStr;
+use num::{BigInt, CheckedMul, One};
+use crate::answer::{self, Answer};
+use crate::options::Options;
 
-fn partition_game_str(line: &str) -> (&str, &str) {
-    let colon_i = line.find(":").expect("No colon found in line.");
-    let game_id_str = &line[5..colon_i];
-    let rounds_str = &line[colon_i+2..];
-    (game_id_str, rounds_str)
+/// The bag used in part 1 if no `bag` option is given.
+const DEFAULT_BAG: &str = "red:12,green:13,blue:14";
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct Colour(String);
+
+/// The number of cubes of each colour in a bag. Colours not in the map have no cubes.
+type Bag = HashMap<Colour, u32>;
+
+/// Parse a bag from a string of the form `red:12,green:13,blue:14`.
+fn parse_bag(s: &str) -> Result<Bag, String> {
+    let mut bag = Bag::new();
+    for entry in s.split(',') {
+        let (colour, number_s) = entry.split_once(':')
+            .ok_or(format!("Bag entry `{entry}` should be in format `<colour>:<number>`."))?;
+        let number = number_s.parse::<u32>()
+            .map_err(|e| format!("Could not parse number of {colour} cubes: {e}"))?;
+        bag.insert(Colour(String::from(colour)), number);
+    }
+    Ok(bag)
+}
+
+/// The reason a game is impossible with a given bag: the first round that shows more cubes of a
+/// colour than the bag holds, and the first such colour listed in that round.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Impossibility {
+    /// The index of the round within the game, counting from 0.
+    round: usize,
+    colour: Colour,
+    shown: u32,
+    available: u32
 }
 
-fn get_samples(round_str: &str) -> Vec<(&str, i32)> {
-    let mut all_samples: Vec<(&str, i32)> = vec!();
-    let rounds = round_str.split("; ");
-    for r in rounds {
-        let samples = r.split(", ");
-        for s in samples {
-            let (number_s, color) = s.split_once(" ")
-                .expect("Text should be in format `<number> <color>");
-            let number = number_s.parse::<i32>()
-                .expect("Could not parse number from text");
-            all_samples.push((color, number))
+/// The number of cubes of each colour shown in a round, in the order they are listed.
+type Round = Vec<(Colour, u32)>;
+
+#[derive(Clone, Debug)]
+struct Game {
+    id: u32,
+    rounds: Vec<Round>
+}
+
+impl FromStr for Game {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (game_id_str, rounds_str) = s.split_once(": ")
+            .ok_or(format!("Line `{s}` should be in format `Game <id>: <rounds>`."))?;
+        let id = game_id_str.strip_prefix("Game ")
+            .ok_or(format!("Could not find game ID in `{game_id_str}`."))?
+            .parse::<u32>()
+            .map_err(|e| format!("Could not parse game ID from text: {e}"))?;
+        let mut rounds: Vec<Round> = vec!();
+        for r in rounds_str.split("; ") {
+            let mut round = Round::new();
+            for sample in r.split(", ") {
+                let (number_s, colour) = sample.split_once(' ')
+                    .ok_or(format!("Text `{sample}` should be in format `<number> <colour>`."))?;
+                let number = number_s.parse::<u32>()
+                    .map_err(|e| format!("Could not parse number from text: {e}"))?;
+                // A colour listed twice in a round counts once, with the numbers added up.
+                match round.iter_mut().find(|(c, _)| c.0 == colour) {
+                    Some((_, shown)) => *shown += number,
+                    None => round.push((Colour(String::from(colour)), number))
+                }
+            }
+            rounds.push(round);
         }
+        Ok(Game { id, rounds })
     }
-    all_samples
 }
 
-fn is_possible(rounds_str: &str, bag: &HashMap<&str, i32>) -> bool {
-    for (color, number) in get_samples(rounds_str) {
-        if number > bag[color] {
-            return false
+impl Game {
+
+    /// The smallest bag that makes the game possible.
+    fn minimal_bag(&self) -> Bag {
+        let mut bag = Bag::new();
+        for round in &self.rounds {
+            for (colour, number) in round {
+                let max = bag.entry(colour.clone()).or_default();
+                *max = (*max).max(*number);
+            }
         }
+        bag
     }
-    true
-}
 
-fn minimum_power(rounds_str: &str) -> i32 {
-    let mut max: HashMap<&str, i32> = HashMap::new();
-    for (color, number) in get_samples(rounds_str) {
-        let current_min = match max.get(color) {
-            Some(val) => *val,
-            None => number - 1
-        };
-        if number > current_min {
-            max.insert(color, number);
+    /// Return why the game is impossible with the given bag, or None if it is possible.
+    fn impossibility(&self, bag: &Bag) -> Option<Impossibility> {
+        for (i, round) in self.rounds.iter().enumerate() {
+            for (colour, shown) in round {
+                let available = bag.get(colour).copied().unwrap_or(0);
+                if *shown > available {
+                    return Some(Impossibility {
+                        round: i,
+                        colour: colour.clone(),
+                        shown: *shown,
+                        available
+                    })
+                }
+            }
         }
+        None
+    }
 
+    fn is_possible(&self, bag: &Bag) -> bool {
+        self.impossibility(bag).is_none()
     }
-    max.values().product::<i32>()
-}
 
-pub(crate) fn part_1(s: &str) -> String {
-    let mut bag = HashMap::new();
-    bag.insert("red", 12);
-    bag.insert("green", 13);
-    bag.insert("blue", 14);
+    /// The product of the numbers of each colour of cube in the minimal bag, or None if it
+    /// overflows `T`.
+    fn power<T>(&self) -> Option<T> where T: CheckedMul + One + From<u32> {
+        self.minimal_bag().values().try_fold(T::one(), |acc, n| acc.checked_mul(&T::from(*n)))
+    }
+}
 
-    let mut total = 0;
+fn get_games(s: &str) -> Vec<Game> {
+    s.lines()
+        .map(|line| Game::from_str(line).expect("Could not parse game."))
+        .collect()
+}
 
-    for line in s.lines() {
-        let (game_id_str, rounds_str) = partition_game_str(line);
-        let game_id = game_id_str.parse::<i32>()
-            .expect("Could not parse game ID from text");
-        if is_possible(rounds_str, &bag) {
-            total += game_id;
+/// Sum the IDs of the games that are possible with the bag given by the `bag` option, in the form
+/// `red:12,green:13,blue:14`. If the `explain` option is set, print why each impossible game is
+/// impossible.
+pub(crate) fn part_1(s: &str, opts: &Options) -> Answer {
+    let bag = parse_bag(opts.get("bag").unwrap_or(DEFAULT_BAG)).expect("Could not parse bag.");
+    let games = get_games(s);
+    if opts.flag("explain") {
+        for g in &games {
+            if let Some(i) = g.impossibility(&bag) {
+                println!(
+                    "Game {}: round {} shows {} {} cubes but the bag only has {}.",
+                    g.id, i.round + 1, i.shown, i.colour.0, i.available
+                );
+            }
         }
     }
-    total.to_string()
+    answer::sum(games.iter().filter(|g| g.is_possible(&bag)).map(|g| g.id), opts)
 }
 
-pub(crate) fn part_2(s: &str) -> String {
-    let mut total = 0;
-    for line in s.lines() {
-        let (_, rounds_str) = partition_game_str(line);
-        total += minimum_power(rounds_str);
+pub(crate) fn part_2(s: &str, opts: &Options) -> Answer {
+    let games = get_games(s);
+    if answer::big_mode(opts) {
+        answer::sum(games.iter().map(|g| g.power::<BigInt>().unwrap()), opts)
+    } else {
+        let powers = games.iter()
+            .map(|g| g.power::<u64>().expect("Power overflowed; try the `big` option."));
+        answer::sum(powers, opts)
     }
-    total.to_string()
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_the_first_colour_listed() {
+        let game = Game::from_str("Game 1: 1 red; 5 green, 1 blue, 9 red, 2 green").unwrap();
+        let bag = parse_bag("red:3,green:4,blue:0").unwrap();
+        assert_eq!(game.impossibility(&bag), Some(Impossibility {
+            round: 1,
+            colour: Colour(String::from("green")),
+            shown: 7,
+            available: 4
+        }));
+        assert_eq!(game.power::<u32>(), Some(63));
+    }
+}
@@ -1,233 +1,303 @@
-use std::cmp;
+use std::ops::Range;
+use std::str::FromStr;
+use num::{BigInt, CheckedAdd, CheckedMul, One, Zero};
+use crate::common::Grid;
+use crate::answer::{self, Answer};
+use crate::options::Options;
 
-/// Iterate over all tiles that are adjacent to any tile between (row, start_col) and (row, end_col)
-/// inclusive.
-struct Adjacent {
+/// A number in the schematic, spanning the columns `cols` of row `row`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Number {
+    value: u32,
     row: usize,
-    start_col: usize,
-    end_col: usize,
-    all_rows: usize,
-    all_cols: usize,
-    i_row: usize,
-    i_col: usize
+    cols: Range<usize>
 }
 
-impl Adjacent {
-    fn new(
-        row: usize,
-        start_col: usize,
-        end_col: usize,
-        all_rows: usize,
-        all_cols: usize
-    ) -> Adjacent {
-        let i_row: usize = row.saturating_sub(1);
-        let i_col: usize = start_col.saturating_sub(1);
-        Adjacent {
-            row,
-            start_col,
-            end_col,
-            all_rows,
-            all_cols,
-            i_row,
-            i_col
-        }
-    }
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct Symbol {
+    c: char,
+    posn: (usize, usize)
 }
-impl Iterator for Adjacent {
-    type Item = (usize, usize);
-    fn next(&mut self) -> Option<Self::Item> {
-        let max_row = cmp::min(self.row + 1, self.all_rows - 1);
-        let max_col = cmp::min(self.end_col + 1, self.all_cols - 1);
-        if self.i_row > max_row {
-            return None
-        }
-        let to_return = (self.i_row, self.i_col);
-
-        if self.i_col >= max_col {
-            // Have reached the end of a row.  We have already checked that we are not at the last
-            // row so move to the start of the next row.
-            self.i_row += 1;
-            self.i_col = self.start_col.saturating_sub(1);
-        } else {
-            // Not at the end of a row so move on to the next column
-            if (self.i_row == self.row)
-                && (self.i_col >= self.start_col && self.i_col <= self.end_col) {
-                // We are inside the number string being checked so fast forward to the end
-                self.i_col = cmp::min(self.end_col + 1, max_col);
-            } else {
-                self.i_col += 1;
+
+/// What, if anything, occupies a cell of the schematic. Numbers and symbols are given by their
+/// index in the schematic's lists.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Cell {
+    Empty,
+    Number(usize),
+    Symbol(usize)
+}
+
+#[derive(Clone, Debug)]
+struct Schematic {
+    numbers: Vec<Number>,
+    symbols: Vec<Symbol>,
+    /// Maps each cell to the number or symbol occupying it.
+    cells: Grid<Cell>
+}
+
+fn is_symbol(c: char) -> bool {
+    ! (c.is_ascii_digit() || c == '.')
+}
+
+impl FromStr for Schematic {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut numbers: Vec<Number> = vec!();
+        let mut symbols: Vec<Symbol> = vec!();
+        let mut cell_rows: Vec<Vec<Cell>> = vec!();
+        for (ir, line) in s.lines().enumerate() {
+            let chars: Vec<char> = line.chars().collect();
+            let mut cell_row: Vec<Cell> = vec!();
+            let mut num_start_col: Option<usize> = None;
+            // Chain a '.' on to the end of the row so that a number at the end of the row is
+            // handled like any other.
+            for (ic, c) in chars.iter().copied().chain(['.']).enumerate() {
+                if c.is_ascii_digit() {
+                    num_start_col.get_or_insert(ic);
+                } else if let Some(start_col) = num_start_col.take() {
+                    // We have encountered a non-digit after a string of digits, ie, we have a
+                    // full number
+                    let value = chars[start_col..ic].iter().collect::<String>().parse::<u32>()
+                        .map_err(|e| format!("Could not parse number on row {ir}: {e}"))?;
+                    numbers.push(Number { value, row: ir, cols: start_col..ic });
+                    cell_row.extend((start_col..ic).map(|_| Cell::Number(numbers.len() - 1)));
+                }
+                if ic == chars.len() {
+                    break
+                }
+                if is_symbol(c) {
+                    symbols.push(Symbol { c, posn: (ir, ic) });
+                    cell_row.push(Cell::Symbol(symbols.len() - 1));
+                } else if !c.is_ascii_digit() {
+                    cell_row.push(Cell::Empty);
+                }
             }
+            cell_rows.push(cell_row);
         }
-        Some(to_return)
+        if cell_rows.is_empty() || cell_rows.iter().any(|r| r.len() != cell_rows[0].len()) {
+            return Err(String::from("Schematic must be a non-empty rectangle."))
+        }
+        Ok(Schematic { numbers, symbols, cells: Grid::from_rows(cell_rows) })
     }
 }
 
-/// Create a 2-d vector from a newline-delimited input string and return a tuple containing the
-/// vector, the number of rows and the number of columns.
-fn get_grid(s: &str) -> (Vec<Vec<char>>, usize, usize) {
-    let mut v: Vec<Vec<char>> = vec!();
-    for line in s.lines() {
-        v.push(line.chars().collect());
+impl Schematic {
+
+    /// Return the indices of the distinct numbers occupying any of the given positions.
+    fn numbers_at<I>(&self, posns: I) -> Vec<usize> where I: Iterator<Item = (usize, usize)> {
+        let mut found: Vec<usize> = posns
+            .filter_map(|p| match self.cells.get(&p) {
+                Some(Cell::Number(i)) => Some(i),
+                _ => None
+            })
+            .collect();
+        found.sort();
+        found.dedup();
+        found
+    }
+
+    /// Return the indices of the numbers adjacent (including diagonally) to the given symbol.
+    fn numbers_adjacent_to_symbol(&self, symbol_i: usize) -> Vec<usize> {
+        let posn = self.symbols[symbol_i].posn;
+        self.numbers_at(self.cells.neighbors(&posn, true).into_iter().flatten())
+    }
+
+    /// Return the indices of the symbols adjacent (including diagonally) to the given number.
+    fn symbols_adjacent_to_number(&self, number_i: usize) -> Vec<usize> {
+        let number = &self.numbers[number_i];
+        let mut found: Vec<usize> = number.cols.clone()
+            .flat_map(|col| self.cells.neighbors(&(number.row, col), true))
+            .flatten()
+            .filter_map(|p| match self.cells.get(&p) {
+                Some(Cell::Symbol(i)) => Some(i),
+                _ => None
+            })
+            .collect();
+        found.sort();
+        found.dedup();
+        found
+    }
+
+    /// For each occurrence of the symbol `c`, return the values of the numbers adjacent to it.
+    fn adjacent_numbers(&self, c: char) -> Vec<Vec<u32>> {
+        self.symbols.iter()
+            .enumerate()
+            .filter(|(_, s)| s.c == c)
+            .map(|(i, _)| self.numbers_adjacent_to_symbol(i).iter()
+                .map(|n| self.numbers[*n].value)
+                .collect())
+            .collect()
+    }
+
+    /// Apply a rule to every occurrence of the rule's symbol, returning the result for each
+    /// occurrence that the rule accepts, or None if any result overflows `T`.
+    fn apply<T>(&self, rule: &SymbolRule) -> Option<Vec<T>>
+        where T: CheckedAdd + CheckedMul + Zero + One + From<u32> {
+        self.adjacent_numbers(rule.symbol).iter()
+            .filter(|values| rule.count.is_none_or(|n| values.len() == n))
+            .map(|values| rule.aggregate.apply(values))
+            .collect()
     }
-    let n_rows = v.len();
-    let n_cols = v[0].len();
-    (v, n_rows, n_cols)
 }
 
-fn is_symbol(c: char) -> bool {
-    ! (c.is_numeric() || c == '.')
+/// How to combine the numbers adjacent to a symbol into a single value.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Aggregate {
+    Sum,
+    Product
 }
 
-/// Check if any of the tiles adjacent to (row, col) contain symbols.
-fn has_adjacent_symbol(
-    grid: &Vec<Vec<char>>,
-    row: usize,
-    start_col: usize,
-    end_col: usize,
-    all_rows: usize,
-    all_cols: usize
-) -> bool {
-    for (r, c) in Adjacent::new(row, start_col, end_col, all_rows, all_cols) {
-        if is_symbol(grid[r][c]) {
-            return true
+impl FromStr for Aggregate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sum" => Ok(Aggregate::Sum),
+            "product" => Ok(Aggregate::Product),
+            other => Err(format!("Unknown aggregate `{other}`."))
         }
     }
-    false
 }
 
-/// Get the entire number that the digit at (row, col) is a part of. Return a tuple containing
-/// the number and the start and end columns of the number string.
-fn get_number(
-    grid: &Vec<Vec<char>>, row: usize, col: usize, all_cols: usize
-) -> (i32, usize, usize) {
-    let mut c = grid[row][col];
-    let mut s = String::from(c);
-    let mut start_col = col;
-    let mut end_col = col;
-    // first go backwards
-    for i in (0..col).rev() {
-        c = grid[row][i];
-        if ! c.is_numeric() {
-            break
+impl Aggregate {
+    /// Combine the values, or return None if the result overflows `T`.
+    fn apply<T>(&self, values: &[u32]) -> Option<T>
+        where T: CheckedAdd + CheckedMul + Zero + One + From<u32> {
+        let mut values = values.iter().map(|v| T::from(*v));
+        match self {
+            Aggregate::Sum => values.try_fold(T::zero(), |acc, v| acc.checked_add(&v)),
+            Aggregate::Product => values.try_fold(T::one(), |acc, v| acc.checked_mul(&v))
         }
-        s.push(c);
-        start_col = i;
     }
-    // now reverse the string and start looking forward
-    s = s.chars().rev().collect::<String>();
-    for i in (col+1)..all_cols {
-        c = grid[row][i];
-        if ! c.is_numeric() {
-            break
-        }
-        s.push(c);
-        end_col = i;
-    }
-    let num = s.parse::<i32>().unwrap();
-    (num, start_col, end_col)
 }
 
-/// Return the gear ratio for position, or None if the character at the position is not a gear.
-fn get_gear_ratio(
-    grid: &Vec<Vec<char>>,
-    row: usize,
-    col: usize,
-    all_rows: usize,
-    all_cols: usize
-) -> Option<i32> {
-    if grid[row][col] != '*' {
-        return None
+/// A rule selecting occurrences of a symbol, optionally only those adjacent to exactly `count`
+/// numbers, and combining each one's adjacent numbers. A gear is a `*` with exactly two adjacent
+/// numbers, whose ratio is their product.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct SymbolRule {
+    symbol: char,
+    count: Option<usize>,
+    aggregate: Aggregate
+}
+
+impl SymbolRule {
+
+    fn gear() -> SymbolRule {
+        SymbolRule { symbol: '*', count: Some(2), aggregate: Aggregate::Product }
     }
-    let mut chr: char;
-    let mut num: i32;
-    let mut start_col: usize;
-    let mut end_col: usize;
-    // A vector containing (number, row, start_col, end_col) for each adjacent number string
-    let mut adj_nums: Vec<(i32, usize, usize, usize)> = vec!();
-
-    'outer: for (r, c) in Adjacent::new(row, col, col, all_rows, all_cols) {
-        for (_, row, s_col, e_col) in &adj_nums {
-            if r == *row && c >= *s_col && c <= *e_col {
-                // We've already found this number
-                continue 'outer
-            }
+
+    /// Build a rule from the `symbol`, `count` and `aggregate` options, with any missing options
+    /// taken from the gear rule. A `count` of `any` accepts any number of adjacent numbers.
+    fn from_options(opts: &Options) -> Result<SymbolRule, String> {
+        let mut rule = SymbolRule::gear();
+        if let Some(symbol) = opts.get("symbol") {
+            let mut chars = symbol.chars();
+            rule.symbol = match (chars.next(), chars.next()) {
+                (Some(c), None) => c,
+                _ => return Err(format!("Symbol `{symbol}` should be a single character."))
+            };
         }
-        chr = grid[r][c];
-        if chr.is_numeric() {
-            if adj_nums.len() == 2 {
-                // We already identified two numbers and now we've identified a third. We therefore
-                // know this isn't a gear so immediately return None
-                return None
-            }
-            (num, start_col, end_col) = get_number(grid, r, c, all_cols);
-            adj_nums.push((num, r, start_col, end_col));
+        match opts.get("count") {
+            None => {},
+            Some("any") => rule.count = None,
+            Some(n) => rule.count = Some(n.parse::<usize>()
+                .map_err(|e| format!("Could not parse count `{n}`: {e}"))?)
+        }
+        if let Some(aggregate) = opts.get("aggregate") {
+            rule.aggregate = Aggregate::from_str(aggregate)?;
         }
+        Ok(rule)
     }
-    if adj_nums.len() == 2 {
-        return Some(adj_nums[0].0 * adj_nums[1].0)
+}
+
+pub(crate) fn part_1(s: &str, opts: &Options) -> Answer {
+    let schematic = Schematic::from_str(s).expect("Could not parse schematic.");
+    let values = (0..schematic.numbers.len())
+        .filter(|i| !schematic.symbols_adjacent_to_number(*i).is_empty())
+        .map(|i| schematic.numbers[i].value);
+    answer::sum(values, opts)
+}
+
+/// Sum the results of applying a symbol rule to the schematic. By default this is the sum of the
+/// gear ratios, but the rule can be changed with the `symbol`, `count` and `aggregate` options.
+pub(crate) fn part_2(s: &str, opts: &Options) -> Answer {
+    let schematic = Schematic::from_str(s).expect("Could not parse schematic.");
+    let rule = SymbolRule::from_options(opts).expect("Invalid symbol rule.");
+    if answer::big_mode(opts) {
+        answer::sum(schematic.apply::<BigInt>(&rule).unwrap(), opts)
+    } else {
+        let results = schematic.apply::<u64>(&rule)
+            .expect("Rule result overflowed; try the `big` option.");
+        answer::sum(results, opts)
     }
-    None
 }
 
-pub(crate) fn part_1(s: &str) -> String {
-    let (grid, all_rows, all_cols) = get_grid(s);
-    let mut num_s: String;
-    let mut num_start_col: Option<usize>;
-    let mut total = 0;
-
-    for (ir, row) in grid.iter().enumerate() {
-        num_start_col = None;
-        num_s = String::new();
-        for (ic, c) in row.iter().enumerate() {
-            if c.is_numeric() {
-                if num_s.is_empty() {
-                    // start of a number
-                    num_start_col = Some(ic);
-                }
-                num_s.push(*c);
-            } else if ! num_s.is_empty() {
-                // We have encountered a non-digit after a string of digits, ie, we have a full
-                // number
-                if has_adjacent_symbol(
-                    &grid, ir, num_start_col.unwrap(), ic.saturating_sub(1), all_rows, all_cols
-                ) {
-                    total += num_s.parse::<i32>().unwrap();
-                }
-                num_start_col = None;
-                num_s = String::new();
-            }
-        }
-        if ! num_s.is_empty() {
-            // Check number string again at the end of the row
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-            if has_adjacent_symbol(
-                &grid, ir, num_start_col.unwrap(), all_cols-1, all_rows, all_cols
-            ) {
+    const EXAMPLE: &str = "\
+467..114..
+...*......
+..35..633.
+......#...
+617*......
+.....+.58.
+..592.....
+......755.
+...$.*....
+.664.598..
+";
 
-                total += num_s.parse::<i32>().unwrap();
+    #[test]
+    fn indexes_numbers_and_symbols() {
+        let schematic = Schematic::from_str("..*\n.12\n#.7\n").unwrap();
+        let values: Vec<u32> = schematic.numbers.iter().map(|n| n.value).collect();
+        assert_eq!(values, vec!(12, 7));
+        // The 7 ends its row.
+        assert_eq!(schematic.numbers[1], Number { value: 7, row: 2, cols: 2..3 });
+        // `*` touches both digits of 12 but lists it once, and the same goes for 12's symbols.
+        assert_eq!(schematic.numbers_adjacent_to_symbol(0), vec!(0));
+        assert_eq!(schematic.symbols_adjacent_to_number(0), vec!(0, 1));
+        assert_eq!(schematic.symbols_adjacent_to_number(1), vec!());
+        assert!(Schematic::from_str("..\n...\n").is_err());
+    }
 
-            }
-        }
+    #[test]
+    fn solves_the_example() {
+        assert_eq!(part_1(EXAMPLE, &Options::default()), Answer::from(4361));
+        assert_eq!(part_2(EXAMPLE, &Options::default()), Answer::from(467835));
+    }
 
+    fn options(args: &[&str]) -> Options {
+        Options::from_args(args.iter().map(|a| String::from(*a)))
     }
-    total.to_string()
-}
 
-pub(crate) fn part_2(s: &str) -> String {
-    let (grid, all_rows, all_cols) = get_grid(s);
-    let mut ratio: Option<i32>;
-    let mut total = 0;
-    for (ir, row) in grid.iter().enumerate() {
-        for (ic, chr) in row.iter().enumerate() {
-            if *chr != '*' {
-                continue
-            }
-            ratio = get_gear_ratio(&grid, ir, ic, all_rows, all_cols);
-            match ratio {
-                Some(number) => total += number,
-                None => continue
-            }
-        }
+    #[test]
+    fn applies_symbol_rules() {
+        let rule = |args: &[&str]| SymbolRule::from_options(&options(args));
+        assert_eq!(rule(&[]), Ok(SymbolRule::gear()));
+        assert_eq!(rule(&["symbol=#", "count=any", "aggregate=sum"]),
+                   Ok(SymbolRule { symbol: '#', count: None, aggregate: Aggregate::Sum }));
+        assert!(rule(&["symbol=##"]).is_err());
+        assert!(rule(&["count=two"]).is_err());
+        assert!(rule(&["aggregate=max"]).is_err());
+
+        // The `*` next to 3, 4 and 5 is not a gear, but is accepted with a count of 3 or any.
+        let schematic = Schematic::from_str("3.4.\n.*..\n5..#\n..2.\n").unwrap();
+        let apply = |args: &[&str]| schematic.apply::<u64>(&rule(args).unwrap());
+        assert_eq!(apply(&[]), Some(vec!()));
+        assert_eq!(apply(&["count=3"]), Some(vec!(60)));
+        assert_eq!(apply(&["count=any", "aggregate=sum"]), Some(vec!(12)));
+        assert_eq!(apply(&["symbol=#", "count=any"]), Some(vec!(2)));
+        assert_eq!(apply(&["symbol=#", "count=2"]), Some(vec!()));
+
+        let answer = part_2(EXAMPLE, &options(&["symbol=#", "count=any", "aggregate=sum"]));
+        assert_eq!(answer, Answer::from(633));
+        let answer = part_2(EXAMPLE, &options(&["count=any", "aggregate=sum"]));
+        assert_eq!(answer, Answer::from(467 + 35 + 617 + 755 + 598));
     }
-    total.to_string()
-}
\ No newline at end of file
+}
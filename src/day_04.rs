@@ -1,59 +1,224 @@
-use std::collections::{HashSet, VecDeque};
+use std::collections::HashSet;
+use std::fmt::Display;
+use std::fs;
+use std::str::FromStr;
+use num::{BigInt, CheckedAdd, One, Zero};
+use crate::answer::{self, Answer};
+use crate::options::Options;
 
-fn get_numbers(s: &str) -> (HashSet<i32>, HashSet<i32>) {
-    let mut winners: HashSet<i32> = HashSet::new();
-    let mut ours: HashSet<i32> = HashSet::new();
+#[derive(Clone, Debug)]
+struct Card {
+    id: u32,
+    winners: HashSet<u32>,
+    ours: Vec<u32>
+}
 
-    let colon_i = s.find(":").expect("Could not find colon in line.");
-    let nums_str = &s[colon_i+2..];
-    let mut num_split = nums_str.split(" | ");
-    for n in num_split.next().unwrap().split_whitespace() {
-        winners.insert(n.parse::<i32>().expect("Could not parse to integer"));
-    }
-    for n in num_split.next().unwrap().split_whitespace() {
-        ours.insert(n.parse::<i32>().expect("Could not parse to integer"));
-    }
-    (winners, ours)
+fn parse_numbers(s: &str) -> Result<Vec<u32>, String> {
+    s.split_whitespace()
+        .map(|n| n.parse::<u32>().map_err(|e| format!("Could not parse `{n}` to integer: {e}")))
+        .collect()
 }
 
-fn get_match_count(s: &str) -> u32 {
-    let (winners, ours) = get_numbers(s);
-    winners.intersection(&ours).count() as u32
+impl FromStr for Card {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (id_str, nums_str) = s.split_once(':')
+            .ok_or(format!("Could not find colon in line `{s}`."))?;
+        let id = id_str.strip_prefix("Card")
+            .ok_or(format!("Line `{s}` does not start with `Card`."))?
+            .trim()
+            .parse::<u32>()
+            .map_err(|e| format!("Could not parse card ID from `{id_str}`: {e}"))?;
+        let (winners_str, ours_str) = nums_str.split_once(" | ")
+            .ok_or(format!("Could not find ` | ` separator in line `{s}`."))?;
+        let winners = parse_numbers(winners_str)?.into_iter().collect();
+        let ours = parse_numbers(ours_str)?;
+        Ok(Card { id, winners, ours })
+    }
 }
 
+impl Card {
 
-pub(crate) fn part_1(s: &str) -> String {
-    let mut total = 0;
-    for line in s.lines() {
-        let our_winners = get_match_count(line);
-        if our_winners > 0 {
-            let score = 2u32.pow(our_winners - 1);
-            total += score;
+    /// The number of our numbers that are winning numbers.
+    fn matches(&self) -> usize {
+        self.ours.iter().filter(|n| self.winners.contains(n)).count()
+    }
+
+    /// The card's points: 1 for the first match, doubled for each match after that. Returns None
+    /// if the points don't fit in a u64.
+    fn points(&self) -> Option<u64> {
+        match self.matches() {
+            0 => Some(0),
+            m => 1u64.checked_shl(m as u32 - 1)
+        }
+    }
+
+    /// The card's points as a big integer, which can't overflow.
+    fn big_points(&self) -> BigInt {
+        match self.matches() {
+            0 => BigInt::zero(),
+            m => BigInt::one() << (m - 1)
         }
     }
-    total.to_string()
 }
 
-pub(crate) fn part_2(s: &str) -> String {
-    let mut additional_copies: VecDeque<u32> = VecDeque::new();
-    let mut num_cards = 0;
+/// The outcome of playing a single card.
+#[derive(Clone, Debug)]
+struct CardTrace<T> {
+    id: u32,
+    matches: usize,
+    points: Option<u64>,
+    /// The number of copies of the card held once every card has been played, including the
+    /// original.
+    copies: T
+}
 
-    for line in s.lines() {
-        let copies = additional_copies.pop_front().unwrap_or(0);
-        let mul = 1 + copies;
-        let mut matches = get_match_count(line);
-        num_cards += 1 + (matches * mul);
-        for m in additional_copies.iter_mut() {
-            if matches == 0 {
-                break
-            }
-            matches -= 1;
-            *m += mul;
+/// Play the cards, where each card's matches win one copy of each of that many following cards.
+/// Copy counts grow exponentially, so they are counted in `T` (eg, u64 or u128) and an error is
+/// returned if they overflow it.
+fn play<T>(cards: &[Card]) -> Result<Vec<CardTrace<T>>, String> where T: CheckedAdd + One + Clone {
+    let matches: Vec<usize> = cards.iter().map(|c| c.matches()).collect();
+    let mut copies: Vec<T> = vec!(T::one(); cards.len());
+    for (i, m) in matches.iter().enumerate() {
+        let won = copies[i].clone();
+        for j in (i + 1)..(i + 1 + m).min(cards.len()) {
+            copies[j] = copies[j].checked_add(&won)
+                .ok_or(format!("Copy count of card {} overflowed.", cards[j].id))?;
         }
-        while matches > 0 {
-            additional_copies.push_back(mul);
-            matches -= 1;
+    }
+    Ok(cards.iter()
+        .zip(matches)
+        .zip(copies)
+        .map(|((card, matches), copies)| CardTrace {
+            id: card.id,
+            matches,
+            points: card.points(),
+            copies
+        })
+        .collect())
+}
+
+/// Render a trace as CSV, one card per line. Points that overflow are left blank.
+fn trace_to_csv<T>(trace: &[CardTrace<T>]) -> String where T: Display {
+    let mut csv = String::from("card,matches,points,copies\n");
+    for t in trace {
+        let points = t.points.map(|p| p.to_string()).unwrap_or_default();
+        csv.push_str(&format!("{},{},{points},{}\n", t.id, t.matches, t.copies));
+    }
+    csv
+}
+
+fn get_cards(s: &str) -> Vec<Card> {
+    s.lines()
+        .map(|line| Card::from_str(line).expect("Could not parse card."))
+        .collect()
+}
+
+/// The file the trace should be written to, if the `trace` option names one rather than being a
+/// bare flag or `false`.
+fn trace_path(opts: &Options) -> Option<&str> {
+    opts.get("trace").filter(|v| !["true", "false"].contains(v))
+}
+
+/// Play the cards and return the total number of cards held, exporting the trace if the `trace`
+/// option is set: to stdout if it is a bare flag, or otherwise to the file it names.
+fn total_cards<T>(cards: &[Card], opts: &Options) -> Answer
+    where T: CheckedAdd + Zero + One + Clone + Display + Into<BigInt> + Into<Answer> {
+    let trace = play::<T>(cards).unwrap_or_else(|e| panic!("{e}"));
+    if opts.flag("trace") {
+        match trace_path(opts) {
+            None => print!("{}", trace_to_csv(&trace)),
+            Some(path) => fs::write(path, trace_to_csv(&trace))
+                .unwrap_or_else(|e| panic!("Could not write trace to {path}: {e}"))
         }
     }
-    num_cards.to_string()
-}
\ No newline at end of file
+    answer::sum(trace.into_iter().map(|t| t.copies), opts)
+}
+
+/// Sum the cards' points. Points are u64 unless the `big` option is set.
+pub(crate) fn part_1(s: &str, opts: &Options) -> Answer {
+    let cards = get_cards(s);
+    if answer::big_mode(opts) {
+        return answer::sum(cards.iter().map(Card::big_points), opts)
+    }
+    let points = cards.iter()
+        .map(|c| c.points().expect("Card points overflowed; try the `big` option."));
+    answer::sum(points, opts)
+}
+
+/// Count the cards held after playing every card. Counts are u64 unless the `width` option is set
+/// to `u128`, or the `big` option is set.
+pub(crate) fn part_2(s: &str, opts: &Options) -> Answer {
+    let cards = get_cards(s);
+    if answer::big_mode(opts) {
+        return total_cards::<BigInt>(&cards, opts)
+    }
+    match opts.get("width") {
+        None | Some("u64") => total_cards::<u64>(&cards, opts),
+        Some("u128") => total_cards::<u128>(&cards, opts),
+        Some(other) => panic!("Unsupported width `{other}`.")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "\
+Card 1: 41 48 83 86 17 | 83 86  6 31 17  9 48 53
+Card 2: 13 32 20 16 61 | 61 30 68 82 17 32 24 19
+Card 3:  1 21 53 59 44 | 69 82 63 72 16 21 14  1
+Card 4: 41 92 73 84 69 | 59 84 76 51 58  5 54 83
+Card 5: 87 83 26 28 32 | 88 30 70 12 93 22 82 36
+Card 6: 31 18 13 56 72 | 74 77 10 23 35 67 36 11
+";
+
+    /// A card whose numbers all match, `matches` times.
+    fn winning_card(id: u32, matches: u32) -> Card {
+        Card { id, winners: (1..=matches).collect(), ours: (1..=matches).collect() }
+    }
+
+    fn options(args: &[&str]) -> Options {
+        Options::from_args(args.iter().map(|a| String::from(*a)))
+    }
+
+    #[test]
+    fn reads_the_trace_destination() {
+        assert!(!options(&[]).flag("trace"));
+        assert!(!options(&["trace=false"]).flag("trace"));
+        assert_eq!(trace_path(&options(&["trace"])), None);
+        assert_eq!(trace_path(&options(&["trace=true"])), None);
+        assert_eq!(trace_path(&options(&["trace=cards.csv"])), Some("cards.csv"));
+    }
+
+    #[test]
+    fn plays_the_example() {
+        let trace = play::<u64>(&get_cards(EXAMPLE)).unwrap();
+        let matches: Vec<usize> = trace.iter().map(|t| t.matches).collect();
+        let points: Vec<Option<u64>> = trace.iter().map(|t| t.points).collect();
+        let copies: Vec<u64> = trace.iter().map(|t| t.copies).collect();
+        assert_eq!(matches, vec!(4, 2, 2, 1, 0, 0));
+        assert_eq!(points, vec!(Some(8), Some(2), Some(2), Some(1), Some(0), Some(0)));
+        assert_eq!(copies, vec!(1, 2, 4, 8, 14, 1));
+        assert_eq!(part_1(EXAMPLE, &Options::default()), Answer::from(13));
+        assert_eq!(part_2(EXAMPLE, &Options::default()), Answer::from(30));
+        let csv = trace_to_csv(&trace);
+        assert!(csv.starts_with("card,matches,points,copies\n1,4,8,1\n2,2,2,2\n"));
+    }
+
+    #[test]
+    fn reports_overflow() {
+        // Each card wins the next ten, so copy counts almost double with each card.
+        let cards: Vec<Card> = (1..=100).map(|id| winning_card(id, 10)).collect();
+        assert_eq!(play::<u64>(&cards).unwrap_err(), "Copy count of card 66 overflowed.");
+        assert!(play::<u128>(&cards).is_ok());
+
+        let card = winning_card(1, 70);
+        assert_eq!(card.points(), None);
+        assert_eq!(card.big_points(), BigInt::one() << 69);
+        let numbers: Vec<String> = (1..=70).map(|n| n.to_string()).collect();
+        let input = format!("Card 1: {} | {}", numbers.join(" "), numbers.join(" "));
+        assert_eq!(part_1(&input, &options(&["big"])), Answer::from(BigInt::one() << 69));
+    }
+}
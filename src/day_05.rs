@@ -1,282 +1,240 @@
-//use std::cmp::{max, min};
 use std::str::FromStr;
-use std::string::ParseError;
 use crate::common::{parse_on_whitespace, split_prefix};
-
-
-#[derive(Copy, Clone, Debug)]
-struct RangeMap {
-    dst_start: i64,
-    src_start: i64,
-    range_len: i64
-}
+use crate::piecewise::{PiecewiseMap, RangeMap, MAX_VALUE};
+use crate::answer::Answer;
+use crate::options::Options;
 
 impl FromStr for RangeMap {
-    type Err = ParseError;
+    type Err = String;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut tokens = s.split_whitespace();
-        let dst_start = tokens.next().expect("Could not find source start in str.")
-            .parse().expect("Could not parse source start from str.");
-        let src_start = tokens.next().expect("Could not find destination start in str.")
-            .parse().expect("Could not parse destination start from str.");
-        let range_len = tokens.next().expect("Could not find range length in str.")
-            .parse().expect("Could not parse range length from str");
-        Ok(RangeMap { dst_start, src_start, range_len })
+        let numbers = parse_on_whitespace::<i64>(s)
+            .map_err(|e| format!("Could not parse numbers from `{s}`: {e}"))?;
+        match numbers[..] {
+            [dst_start, src_start, range_len] => Ok(RangeMap { dst_start, src_start, range_len }),
+            _ => Err(format!(
+                "Expected destination start, source start and range length in `{s}`."
+            ))
+        }
     }
 }
 
-impl RangeMap {
-    fn get(&self, k: i64) -> Option<i64> {
-        if k < self.src_start || k >= self.src_start + self.range_len {
-            return None
-        }
-        Some(self.dst_start + (k - self.src_start))
-    }
+/// One of the almanac's maps, converting numbers of one category (eg, "seed") to numbers of
+/// another (eg, "soil").
+#[derive(Clone, Debug)]
+struct CategoryMap {
+    from: String,
+    to: String,
+    map: PiecewiseMap
+}
 
-/*
-    /// Return three optional sub-ranges of the given range: one range of values below this range,
-    /// one range of values within this range and one range of values above this range.
-    fn get_range(&self, start: i64, end: i64) -> (
-        Option<(i64, i64)>,
-        Option<(i64, i64)>,
-        Option<(i64, i64)>
-    ) {
-        let src_end = self.src_start + self.range_len;
-        let mut below: Option<(i64, i64)> = None;
-        let mut matches: Option<(i64, i64)> = None;
-        let mut above: Option<(i64, i64)> = None;
-        if start < self.src_start {
-            below = Some((start, min(end, self.src_start-1)));
-        }
-        if end > src_end {
-            above = Some((max(start, src_end), end));
-        }
-        if start > self.src_start || end < src_end {
-            matches = Some((max(start, self.src_start), min(end, src_end)));
-        }
-        (below, matches, above)
-    }
+impl CategoryMap {
 
-    /// Return how much would need to be added to a given source value to map it to a destination
-    /// value.
-    fn get_increment(&self) -> i64 {
-        self.dst_start - self.src_start
+    /// Parse a header of the form `<from>-to-<to> map:` into the source and destination
+    /// categories.
+    fn parse_header(s: &str) -> Result<(String, String), String> {
+        let names = s.strip_suffix(" map:")
+            .ok_or(format!("Map header `{s}` does not end with ` map:`."))?;
+        let (from, to) = names.split_once("-to-")
+            .ok_or(format!("Map header `{s}` is not of the form `<from>-to-<to> map:`."))?;
+        Ok((String::from(from), String::from(to)))
     }
-
-    /// The end of the source range represented by this range map
-    fn src_end(&self) -> i64 {
-        self.src_start + self.range_len - 1
-    }
-
-
- */
-
 }
 
-fn get_all(k: i64, maps: &Vec<RangeMap>) -> Option<i64> {
-    for m in maps {
-        if let Some(v) = m.get(k) {
-            return Some(v)
-        }
-    }
-    None
+#[derive(Clone, Debug)]
+struct Almanac {
+    seeds: Vec<i64>,
+    maps: Vec<CategoryMap>
 }
 
-pub(crate) fn part_1(s: &str) -> String {
+impl FromStr for Almanac {
+    type Err = String;
 
-    let mut lines = s.lines();
-    let seed_line = lines.next().expect("Could not find seed line.");
-    let (_, seed_str) = split_prefix(seed_line);
-    let seeds: Vec<i64> = parse_on_whitespace(seed_str).expect("Could not parse seeds from seed line.");
-    lines.next();  // Get first blank line out of the way
-    lines.next();  // Get the first header out of the way
-    let mut range_maps: Vec<Vec<RangeMap>> = vec!();
-    let mut current_map_vec: Vec<RangeMap> = vec!();
-    for line in lines {
-        if line == "" {
-            range_maps.push(current_map_vec.clone());
-        } else if line.chars().next().unwrap().is_alphabetic() {
-            // We found a "heading" indicating the start of the next list of mappings
-            current_map_vec = vec!();
-        } else {
-            current_map_vec.push(RangeMap::from_str(line).unwrap())
-        }
-    }
-    range_maps.push(current_map_vec.clone());
-    let mut min_loc: Option<i64> = None;
-    for seed in seeds {
-        let mut k = seed;
-        for v in &range_maps {
-            k = get_all(k, v).unwrap_or(k);
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = s.lines();
+        let seed_line = lines.next().ok_or("Could not find seed line.")?;
+        let (_, seed_str) = split_prefix(seed_line);
+        let seeds: Vec<i64> = parse_on_whitespace(seed_str)
+            .map_err(|e| format!("Could not parse seeds from seed line: {e}"))?;
+        if let Some(seed) = seeds.iter().find(|s| !(0..=MAX_VALUE).contains(*s)) {
+            return Err(format!("Seed {seed} is not between 0 and {MAX_VALUE}."))
         }
-        if k < min_loc.unwrap_or(k+1) {
-            min_loc = Some(k)
+
+        let mut maps: Vec<CategoryMap> = vec!();
+        let mut header: Option<(String, String)> = None;
+        let mut current_map_vec: Vec<RangeMap> = vec!();
+        // Chain an empty line on to the end so that the last map is handled like the others.
+        for line in lines.chain([""]) {
+            // A map ends at an empty line or at the next map's header.
+            let is_header = line.starts_with(|c: char| c.is_alphabetic());
+            if line.is_empty() || is_header {
+                if let Some((from, to)) = header.take() {
+                    let map = PiecewiseMap::new(current_map_vec)
+                        .map_err(|e| format!("Invalid {from}-to-{to} map: {e}"))?;
+                    maps.push(CategoryMap { from, to, map });
+                    current_map_vec = vec!();
+                }
+                if is_header {
+                    header = Some(CategoryMap::parse_header(line)?);
+                }
+            } else if header.is_some() {
+                current_map_vec.push(RangeMap::from_str(line)?);
+            } else {
+                return Err(format!("Found mapping `{line}` before any map header."))
+            }
         }
+        Ok(Almanac { seeds, maps })
     }
-    min_loc.expect("Could not find lowest location.").to_string()
 }
 
-pub(crate) fn part_2(s: &str) -> String {
-
-    let mut lines = s.lines();
-    let seed_line = lines.next().expect("Could not find seed line.");
-    let (_, seed_str) = split_prefix(seed_line);
-    let seed_ranges_desc: Vec<i64> = parse_on_whitespace(seed_str)
-        .expect("Could not parse seeds from seed line.");
-    let mut seed_ranges: Vec<(i64, i64)> = vec!();
+impl Almanac {
 
-    for i in (0..seed_ranges_desc.len()-1).step_by(2) {
-        let start = seed_ranges_desc[i];
-        let range = seed_ranges_desc[i+1];
-        let end = start + range;
-        seed_ranges.push((start, end));
+    /// The seed line read as pairs of start and length, returned as (start, end) with the end
+    /// exclusive. Returns an error if any range ends beyond [`MAX_VALUE`].
+    fn seed_ranges(&self) -> Result<Vec<(i64, i64)>, String> {
+        self.seeds.chunks_exact(2)
+            .map(|r| match r[0] + r[1] {
+                end if end <= MAX_VALUE => Ok((r[0], end)),
+                _ => Err(format!("Seed range {} + {} ends beyond {MAX_VALUE}.", r[0], r[1]))
+            })
+            .collect()
     }
 
-    lines.next();
-    lines.next();
-    let mut range_maps: Vec<Vec<RangeMap>> = vec!();
-    let mut current_map_vec: Vec<RangeMap> = vec!();
-    for line in lines {
-        if line == "" {
-            range_maps.push(current_map_vec.clone());
-        } else if line.chars().next().unwrap().is_alphabetic() {
-            // We found a "heading" indicating the start of the next list of mappings
-            current_map_vec = vec!();
-        } else {
-            let numbers = parse_on_whitespace::<i64>(line)
-                .expect("Could not parse numbers from string.");
-            current_map_vec.push(RangeMap {
-                dst_start: numbers[1],
-                src_start: numbers[0],
-                range_len: numbers[2]
-            })
+    /// Compose the maps leading from the `from` category to the `to` category, if `to` can be
+    /// reached from `from` by following the almanac's maps forwards.
+    fn forward_map(&self, from: &str, to: &str) -> Option<PiecewiseMap> {
+        let mut composed = PiecewiseMap::default();
+        let mut category = from;
+        // Each map can only be used once, which stops us looping forever on a cyclic almanac.
+        for _ in 0..self.maps.len() {
+            if category == to {
+                return Some(composed)
+            }
+            let next = self.maps.iter().find(|m| m.from == category)?;
+            composed = composed.compose(&next.map);
+            category = &next.to;
         }
+        if category == to { Some(composed) } else { None }
     }
-    range_maps.push(current_map_vec.clone());
-    range_maps.reverse();
-    let mut loc = 0;
-    loop {
-        let mut k = loc;
-        for v in &range_maps {
-            k = get_all(k, &v).unwrap_or(k);
+
+    /// Return a single map converting numbers of the `from` category to numbers of the `to`
+    /// category, following the almanac's maps backwards if necessary.
+    fn map_between(&self, from: &str, to: &str) -> Result<PiecewiseMap, String> {
+        if let Some(map) = self.forward_map(from, to) {
+            return Ok(map)
         }
-        for (start, end) in &seed_ranges {
-            if k > *start && k < *end {
-                return loc.to_string()
-            }
+        match self.forward_map(to, from) {
+            Some(map) => map.invert(),
+            None => Err(format!("No chain of maps connects categories {from} and {to}."))
         }
-        loc += 1;
     }
 }
-/*
-fn incr_range(range: &(i64, i64), n: i64) -> (i64, i64) {
-    (range.0 + n, range.1 + n)
+
+pub(crate) fn part_1(s: &str, _opts: &Options) -> Answer {
+    let almanac = Almanac::from_str(s).expect("Could not parse almanac.");
+    let seed_to_loc = almanac.map_between("seed", "location")
+        .expect("Could not map seeds to locations.");
+    almanac.seeds.iter()
+        .map(|seed| seed_to_loc.get(*seed))
+        .min()
+        .expect("Could not find lowest location.")
+        .into()
 }
 
-pub(crate) fn part_2_fast(s: &str) -> String {
-    let mut lines = s.lines();
-    let seed_line = lines.next().expect("Could not find seed line.");
-    let (_, seed_str) = split_prefix(seed_line);
-    let seed_ranges_desc: Vec<i64> = parse_str(seed_str)
-        .expect("Could not parse seeds from seed line.");
-    let mut seed_ranges: Vec<(i64, i64)> = vec!();
+pub(crate) fn part_2(s: &str, _opts: &Options) -> Answer {
+    let almanac = Almanac::from_str(s).expect("Could not parse almanac.");
+    let seed_to_loc = almanac.map_between("seed", "location")
+        .expect("Could not map seeds to locations.");
+    almanac.seed_ranges()
+        .unwrap_or_else(|e| panic!("{e}"))
+        .iter()
+        .filter_map(|(start, end)| seed_to_loc.min_image(*start, *end))
+        .min()
+        .expect("Could not find lowest location.")
+        .into()
+}
 
-    for i in (0..seed_ranges_desc.len()-1).step_by(2) {
-        let start = seed_ranges_desc[i];
-        let range = seed_ranges_desc[i+1];
-        let end = start + range;
-        seed_ranges.push((start, end));
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "\
+seeds: 79 14 55 13
+
+seed-to-soil map:
+50 98 2
+52 50 48
+
+soil-to-fertilizer map:
+0 15 37
+37 52 2
+39 0 15
+
+fertilizer-to-water map:
+49 53 8
+0 11 42
+42 0 7
+57 7 4
+
+water-to-light map:
+88 18 7
+18 25 70
+
+light-to-temperature map:
+45 77 23
+81 45 19
+68 64 13
+
+temperature-to-humidity map:
+0 69 1
+1 0 69
+
+humidity-to-location map:
+60 56 37
+56 93 4
+";
+
+    #[test]
+    fn solves_the_example() {
+        assert_eq!(part_1(EXAMPLE, &Options::default()), Answer::from(35));
+        assert_eq!(part_2(EXAMPLE, &Options::default()), Answer::from(46));
     }
 
-    lines.next();
-    lines.next();
-
-    let mut range_maps: Vec<Vec<RangeMap>> = vec!();
-    let mut current_map_vec: Vec<RangeMap> = vec!();
-    for line in lines {
-        if line == "" {
-            range_maps.push(current_map_vec.clone());
-        } else if line.chars().next().unwrap().is_alphabetic() {
-            // We found a "heading" indicating the start of the next list of mappings
-            current_map_vec = vec!();
-        } else {
-            current_map_vec.push(RangeMap::from_str(line).unwrap())
-        }
+    #[test]
+    fn parses_headers() {
+        let header = CategoryMap::parse_header("seed-to-soil map:");
+        assert_eq!(header, Ok((String::from("seed"), String::from("soil"))));
+        assert!(CategoryMap::parse_header("seed-to-soil").is_err());
+        assert!(CategoryMap::parse_header("seed-soil map:").is_err());
+        assert!(Almanac::from_str("seeds: 1\n\n1 2 3\n").is_err());
+
+        // A header ends the previous map even without an empty line before it.
+        let almanac = Almanac::from_str(
+            "seeds: 1\nseed-to-soil map:\n100 1 1\nsoil-to-location map:\n0 500 1"
+        ).unwrap();
+        let seed_to_loc = almanac.map_between("seed", "location").unwrap();
+        assert_eq!(seed_to_loc.get(1), 100);
+        assert_eq!(seed_to_loc.get(500), 0);
     }
-    range_maps.push(current_map_vec.clone());
 
-    let mut ranges: Vec<(i64, i64)> = seed_ranges;
-    for map_vec in range_maps {
-        println!("Reached next round");
-        let mut sorted_map_vec = map_vec.clone();
-        sorted_map_vec.sort_by_key(|mr| mr.src_start);
-        ranges.sort_by_key(|r| r.0);
-        //println!("{:?}", ranges);
-        let mut i = 0;
-        let mut leftover: Option<(i64, i64)> = None;
-        let mut next_ranges: Vec<(i64, i64)> = vec!();
+    #[test]
+    fn includes_the_first_seed_of_each_range() {
+        // Only seed 10 maps to 0, so the lowest location comes from the first seed of a range.
+        let maps = "\n\nseed-to-location map:\n0 10 1\n";
+        assert_eq!(part_2(&format!("seeds: 10 5{maps}"), &Options::default()), Answer::from(0));
+        // The range 9..10 stops just short of seed 10.
+        assert_eq!(part_2(&format!("seeds: 9 1{maps}"), &Options::default()), Answer::from(9));
+    }
 
-        loop {
-            //println!("{i}");
-            let mut matched = false;
-            let (start, end) = match leftover {
-                // If we have a leftover value, use it and don't increment i
-                Some(range) => range,
-                None => {
-                    if i > ranges.len() - 1 {
-                        break;
-                    }
-                    let range = ranges[i];
-                    i += 1;
-                    range
-                }
-            };
-            //println!("Start: {leftover:?}");
-            leftover = None;
-            for range_map in &sorted_map_vec {
-                //println!("{:?}", range_map);
-                //println!("{}, {}", range_map.src_end(), start);
-                println!("Comparing range map {range_map:?} against range ({start}, {end}).");
-                if range_map.src_end() <= start {
-                    println!("Range map below start of range; continuing.");
-                    continue
-                }
-                if range_map.src_start > end {
-                    println!("Range map above end of range; breaking.");
-                    break
-                }
-                let (below, matches, above) = range_map.get_range(start, end);
-                if let Some(below_range) = below {
-                    println!("Found {below_range:?} below range map. Adding.");
-                    next_ranges.push(below_range);
-                    matched = true;
-                }
-                if let Some(matches_range) = matches {
-                    let to_add = incr_range(&matches_range, range_map.get_increment());
-                    println!("Found {matches_range:?} in range map. Adding {to_add:?}");
-                    next_ranges.push(to_add);
-                    matched = true;
-                }
-                if ! above.is_none() {
-                    let above_range = above.unwrap();
-                    println!("Found {above_range:?} above range. Testing this next.");
-                    leftover = above;
-                    matched = true;
-                }
-                //println!("End: {leftover:?}");
-            }
-            if ! matched {
-                // We didn't match any range so push the whole range through
-                println!("Didn't match ({start}, {end}). Pushing through.");
-                next_ranges.push((start, end))
-            }
+    #[test]
+    fn maps_backwards_by_inverting() {
+        let almanac = Almanac::from_str(EXAMPLE).unwrap();
+        let loc_to_seed = almanac.map_between("location", "seed").unwrap();
+        let seed_to_loc = almanac.map_between("seed", "location").unwrap();
+        for seed in 0..120 {
+            assert_eq!(loc_to_seed.get(seed_to_loc.get(seed)), seed);
         }
-        next_ranges.sort_by_key(|r| r.0);
-        ranges = next_ranges;
+        assert_eq!(loc_to_seed.get(35), 13);
+        assert!(almanac.map_between("seed", "colour").is_err());
     }
-    //println!("{:?}", ranges);
-    ranges[0].0.to_string()
 }
-
- */
\ No newline at end of file
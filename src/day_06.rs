@@ -1,51 +1,132 @@
+use num::{BigInt, CheckedMul, Integer};
+use num::integer::Roots;
 use crate::common::{parse_on_whitespace, split_prefix};
+use crate::answer::{self, Answer};
+use crate::options::Options;
 
-fn count_winning_moves(t: i64, d: i64) -> i64 {
-    // "-b formula" for quadratic equations
-    // a = -1, b = t, c = -d
-    let tf = t as f64;
-    let df = d as f64;
-    let r1: f64 = (-tf + (tf.powi(2) - (4.0 * (df + 0.5))).sqrt()) / -2.0;
-    let r2: f64 = (-tf - (tf.powi(2) - (4.0 * (df + 0.5))).sqrt()) / -2.0;
-    let diff: f64;
-    if r1 > r2 {
-        diff = r1.ceil() - r2;
-    } else {
-        diff = r2.ceil() - r1;
-    }
-    return diff.abs() as i64;
+/// Count the number of ways to beat the record distance `d` in a race lasting `t` milliseconds,
+/// ie, the number of integers x between 0 and t such that x * (t - x) > d.
+///
+/// Works on any integer type, including big integers, but returns None if `t * t` overflows it.
+fn count_winning_moves<T>(t: &T, d: &T) -> Option<T>
+    where T: Integer + Roots + CheckedMul + Clone {
+    let two = T::one() + T::one();
+    // Every product below is at most t * t, and as the record is beaten at t / 2 the record is
+    // less than t * t / 4, so only this product needs checking.
+    let t_squared = t.checked_mul(t)?;
+    let beats = |x: &T| x.clone() * (t.clone() - x.clone()) > *d;
+
+    // The distance is greatest when the button is held for half of the race, so if that doesn't
+    // win then nothing does.
+    let half = t.clone() / two.clone();
+    if !beats(&half) {
+        return Some(T::zero())
+    }
+
+    // Estimate the lower root of x^2 - tx + d = 0 using the integer square root of the
+    // discriminant, then correct the estimate to the first winning x. As the estimate is at most
+    // one away, each loop runs no more than a couple of times.
+    let disc = t_squared - (two.clone() + two.clone()) * d.clone();
+    let mut low = (t.clone() - disc.sqrt()) / two.clone();
+    while !beats(&low) {
+        low = low + T::one();
+    }
+    while low > T::zero() && beats(&(low.clone() - T::one())) {
+        low = low - T::one();
+    }
+    // Winning moves are symmetric about t / 2, so the last winning x is t - low.
+    Some(t.clone() - two * low + T::one())
 }
 
-pub(crate) fn part_1(s: &str) -> String {
+pub(crate) fn part_1(s: &str, opts: &Options) -> Answer {
     let mut lines = s.lines();
     let t_line = lines.next().expect("Couldn't find time line.");
     let d_line = lines.next().expect("Couldn't find distance line.");
 
-    let (_, time_s) = split_prefix(&t_line);
-    let (_, dist_s) = split_prefix(&d_line);
+    let (_, time_s) = split_prefix(t_line);
+    let (_, dist_s) = split_prefix(d_line);
 
-    let times = parse_on_whitespace::<i64>(&time_s)
+    let times = parse_on_whitespace::<u64>(time_s)
         .expect("Could not parse times.");
-    let distances = parse_on_whitespace::<i64>(&dist_s)
+    let distances = parse_on_whitespace::<u64>(dist_s)
         .expect("Could not parse distances.");
 
-    let mut product = 1;
+    let counts = times.iter()
+        .zip(distances.iter())
+        .map(|(t, d)| count_winning_moves(t, d).expect("Race time overflowed when squared."));
+    answer::product(counts, opts)
+}
+
+/// Read the digits of a line as a single number, ignoring the spaces between them.
+fn parse_kerned<T>(line: Option<&str>, name: &str) -> T where T: std::str::FromStr {
+    line.unwrap_or_else(|| panic!("Could not find {name} line."))
+        .chars().filter(|c| c.is_ascii_digit())
+        .collect::<String>().parse::<T>()
+        .unwrap_or_else(|_| panic!("Could not parse {name}; try the `big` option."))
+}
 
-    for (i, t) in times.iter().enumerate() {
-        let n = count_winning_moves(*t, distances[i]);
-        product *= n;
+/// Count the ways to win the single long race, using u128 unless the `big` option is set.
+pub(crate) fn part_2(s: &str, opts: &Options) -> Answer {
+    let mut lines = s.lines();
+    let (t_line, d_line) = (lines.next(), lines.next());
+    if answer::big_mode(opts) {
+        let t: BigInt = parse_kerned(t_line, "time");
+        let d: BigInt = parse_kerned(d_line, "distance");
+        return count_winning_moves(&t, &d).expect("Big integers cannot overflow.").into()
     }
-    product.to_string()
+    let t: u128 = parse_kerned(t_line, "time");
+    let d: u128 = parse_kerned(d_line, "distance");
+    count_winning_moves(&t, &d)
+        .expect("Race time overflowed when squared; try the `big` option.")
+        .into()
 }
 
-pub(crate) fn part_2(s: &str) -> String {
-    let mut lines = s.lines();
-    let t = lines.next().expect("Could not find time line.")
-        .chars().filter(|c| c.is_numeric())
-        .collect::<String>().parse::<i64>().expect("Could not parse time to string.");
-    let d = lines.next().expect("Could not find distance line.")
-        .chars().filter(|c| c.is_numeric())
-        .collect::<String>().parse::<i64>().expect("Could not parse distance to string.");
-
-    count_winning_moves(t, d).to_string()
-}
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use num::BigInt;
+    use super::*;
+
+    fn brute_force(t: u64, d: u64) -> u64 {
+        (0..=t).filter(|x| x * (t - x) > d).count() as u64
+    }
+
+    #[test]
+    fn matches_brute_force() {
+        for t in 0..60 {
+            for d in 0..(t * t / 4 + 3) {
+                let expected = Some(brute_force(t, d));
+                assert_eq!(count_winning_moves(&t, &d), expected, "t = {t}, d = {d}");
+            }
+        }
+    }
+
+    #[test]
+    fn perfect_square_discriminant() {
+        // 10 * 20 = 200, so holding for 10 ties the record rather than beating it.
+        assert_eq!(count_winning_moves(&30u64, &200u64), Some(9));
+    }
+
+    /// Check that the count implies a first winning move that wins and is preceded by one that
+    /// doesn't.
+    fn assert_boundary<T>(t: T, d: T)
+        where T: Integer + Roots + CheckedMul + Clone + std::fmt::Debug {
+        let wins = count_winning_moves(&t, &d).unwrap();
+        let low = (t.clone() - wins + T::one()) / (T::one() + T::one());
+        assert!(low.clone() * (t.clone() - low.clone()) > d);
+        assert!((low.clone() - T::one()) * (t - low + T::one()) <= d);
+    }
+
+    #[test]
+    fn beyond_f64_precision() {
+        let t: u128 = (1 << 60) + 7;
+        let d: u128 = (1 << 118) + 12_345_678_901_234_567;
+        assert_boundary(t, d);
+        assert_boundary(BigInt::from(t) << 64, BigInt::from(d) << 128);
+    }
+
+    #[test]
+    fn overflow_is_reported() {
+        assert_eq!(count_winning_moves(&(1u64 << 32), &0), None);
+        assert_eq!(count_winning_moves(&((1u64 << 32) - 1), &0), Some((1 << 32) - 2));
+    }
+}
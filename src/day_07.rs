@@ -1,121 +1,298 @@
+use std::cmp::Ordering;
 use std::collections::HashMap;
+use num::BigInt;
+use crate::answer::{self, Answer};
+use crate::options::Options;
 
-#[derive(Copy, Clone, Debug)]
-struct Hand {
-    card_values: [u32; 5],
-    type_value: u32
+/// Return every partition of `n` into positive parts, each with its parts in descending order.
+/// The partitions are sorted in ascending lexicographic order, which is also the order of the
+/// strength of the hand types they describe (eg, two pair, `[2, 2, 1]`, beats one pair,
+/// `[2, 1, 1, 1]`).
+fn partitions(n: u32) -> Vec<Vec<u32>> {
+    fn extend(remaining: u32, max_part: u32, current: &mut Vec<u32>, all: &mut Vec<Vec<u32>>) {
+        if remaining == 0 {
+            all.push(current.clone());
+            return
+        }
+        for part in (1..=remaining.min(max_part)).rev() {
+            current.push(part);
+            extend(remaining - part, part, current, all);
+            current.pop();
+        }
+    }
+    let mut all: Vec<Vec<u32>> = vec!();
+    extend(n, n, &mut vec!(), &mut all);
+    all.sort();
+    all
 }
 
-impl Hand {
-    fn from_str(s: &str, joker_rule: bool) -> Result<Self, String> {
-        let j_val = if joker_rule { 1 } else { 11 };
-        let mut card_values = [0; 5];
-        let mut card_counts: HashMap<char, i32> = HashMap::new();
-        for (i, c) in s.chars().enumerate() {
-            if c.is_numeric() {
-                card_values[i] = match c.to_digit(10) {
-                    Some(value) => value,
-                    None => return Err(format!("Couldn't convert character '{c}' to number."))
-                }
+/// The rules for a game of camel cards.
+#[derive(Clone, Debug)]
+struct Rules {
+    /// Every valid card, from weakest to strongest.
+    rank_order: Vec<char>,
+    /// Cards that act as whichever card would make the hand's type strongest.
+    wildcards: Vec<char>,
+    hand_size: usize,
+    /// The count signature of each hand type, from weakest to strongest.
+    hand_types: Vec<Vec<u32>>
+}
+
+impl Rules {
+
+    fn new(rank_order: &str, wildcards: &str, hand_size: usize) -> Rules {
+        Rules {
+            rank_order: rank_order.chars().collect(),
+            wildcards: wildcards.chars().collect(),
+            hand_size,
+            hand_types: partitions(hand_size as u32)
+        }
+    }
+
+    /// The rules for part 1.
+    fn standard() -> Rules {
+        Rules::new("23456789TJQKA", "", 5)
+    }
+
+    /// The rules for part 2, where J is a joker that is the weakest card for ranking purposes.
+    fn jokers() -> Rules {
+        Rules::new("J23456789TQKA", "J", 5)
+    }
+
+    fn card_value(&self, c: char) -> Result<u32, String> {
+        match self.rank_order.iter().position(|r| *r == c) {
+            Some(i) => Ok(i as u32),
+            None => Err(format!("Unexpected card value {c}."))
+        }
+    }
+
+    /// Return the count signature of a hand: the number of times each distinct card appears,
+    /// sorted in descending order, with any wildcards added to the largest count.
+    fn signature(&self, cards: &[char]) -> Vec<u32> {
+        let mut card_counts: HashMap<char, u32> = HashMap::new();
+        let mut wild_count = 0;
+        for c in cards {
+            if self.wildcards.contains(c) {
+                wild_count += 1;
             } else {
-                card_values[i] = match c {
-                    'T' => 10,
-                    'J' => j_val,
-                    'Q' => 12,
-                    'K' => 13,
-                    'A' => 14,
-                    _ => return Err(format!("Unexpected card value {c}.")),
-                }
+                *card_counts.entry(*c).or_default() += 1;
             }
-            let count = card_counts.entry(c).or_insert(0);
-            *count += 1;
         }
+        let mut counts: Vec<u32> = card_counts.into_values().collect();
+        counts.sort_by(|a, b| b.cmp(a));
+        match counts.first_mut() {
+            Some(highest) => *highest += wild_count,
+            None => counts.push(wild_count)
+        }
+        counts
+    }
 
-        let j_count = if joker_rule {
-            let c = *card_counts.entry('J').or_default();
-            if c < 5 {
-                // Don't remove if all cards are J as then we would be left with an empty list.
-                card_counts.remove(&'J');
-            }
-            Some(c)
-        } else {
-            None
-        };
-
-        let mut most_common = match card_counts.keys().max_by_key(|k| card_counts[*k]) {
-            Some(value) => value,
-            None => return Err(format!("Could not find most common card in hand {s}."))
-        };
-
-        if let Some(c) = j_count {
-            let count = card_counts.entry(*most_common).or_default();
-            *count += c;
-
-            // Re-calculate now that we have added the jokers
-            most_common = match card_counts.keys().max_by_key(|k| card_counts[*k]) {
-                Some(value) => value,
-                None => return Err(format!("Could not find most common card in hand {s}."))
-            };
-        };
-
-        let highest_count = card_counts[most_common];
-
-        let type_value = match card_counts.len() {
-            5 => 0,  // High card
-            4 => 1,  // One pair
-            3 => {
-                match highest_count {
-                    2 => 2,  // Two pair
-                    3 => 3,  // Three of a kind
-                    other => return Err(
-                        format!("Length of card_counts is 3 but highest count is {other}.")
-                    )
-                }
-            },
-            2 => {
-                match highest_count {
-                    3 => 4,  // Full house
-                    4 => 5,  // Four of a kind
-                    other => return Err(
-                        format!("Length of card_counts is 2 but highest count is {other}.")
-                    )
-                }
-            },
-            1 => 6, // Five of a kind
-            other => return Err(format!("Length of card_counts is {other}."))
-        };
+    /// Return the index of the hand's type in `hand_types`, ie, 0 for the weakest type.
+    fn classify(&self, cards: &[char]) -> Result<usize, String> {
+        let signature = self.signature(cards);
+        self.hand_types.binary_search(&signature)
+            .map_err(|_| format!("Count signature {signature:?} does not match any hand type."))
+    }
+}
+
+/// A hand of cards. Hands are ordered by type first and then by the values of their cards, in the
+/// order they were dealt.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Hand {
+    card_values: Vec<u32>,
+    type_value: usize
+}
+
+impl Hand {
+    fn from_str(s: &str, rules: &Rules) -> Result<Self, String> {
+        let cards: Vec<char> = s.chars().collect();
+        if cards.len() != rules.hand_size {
+            return Err(format!("Hand {s} has {} cards, not {}.", cards.len(), rules.hand_size))
+        }
+        let card_values = cards.iter()
+            .map(|c| rules.card_value(*c))
+            .collect::<Result<Vec<u32>, String>>()?;
+        let type_value = rules.classify(&cards)?;
         Ok(Hand { card_values, type_value })
     }
 }
 
-fn process_hands(s: &str, joker_rule: bool) -> String {
-    let mut hands: [Vec<(Hand, i32)>; 7] = Default::default();
+impl Ord for Hand {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.type_value.cmp(&other.type_value)
+            .then_with(|| self.card_values.cmp(&other.card_values))
+    }
+}
+
+impl PartialOrd for Hand {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// The result of ranking a hand against all the others.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct RankedHand {
+    /// The position of the hand in the input.
+    index: usize,
+    bid: u64,
+    /// 1 for the weakest hand. Hands that tie share the rank of the first of them, and the next
+    /// stronger hand's rank skips over the rest (ie, "1224" ranking).
+    rank: usize,
+    /// The bid multiplied by the rank, or None if that overflows.
+    winnings: Option<u64>,
+    /// Whether another hand is identical to this one.
+    tied: bool
+}
+
+/// Rank each of the given hands, returning the results in the same order as the hands.
+fn rank_hands(hands: &[(Hand, u64)]) -> Vec<RankedHand> {
+    let mut order: Vec<usize> = (0..hands.len()).collect();
+    order.sort_by(|a, b| hands[*a].0.cmp(&hands[*b].0));
+    let mut ranked: Vec<Option<RankedHand>> = vec!(None; hands.len());
+    let mut lower_ranked = 0;
+    for group in order.chunk_by(|a, b| hands[*a].0 == hands[*b].0) {
+        let rank = lower_ranked + 1;
+        lower_ranked += group.len();
+        for index in group {
+            let bid = hands[*index].1;
+            ranked[*index] = Some(RankedHand {
+                index: *index,
+                bid,
+                rank,
+                winnings: bid.checked_mul(rank as u64),
+                tied: group.len() > 1
+            });
+        }
+    }
+    ranked.into_iter().map(|r| r.expect("Every hand should have been ranked.")).collect()
+}
+
+/// Return the total winnings of the hands under `rules`. The `ties` option reports how many hands
+/// are tied with an identical hand.
+fn process_hands(s: &str, rules: &Rules, opts: &Options) -> Answer {
+    let mut hands: Vec<(Hand, u64)> = vec!();
     for line in s.lines() {
         let mut split = line.split_whitespace();
-        let hand = Hand::from_str(split.next().expect("Couldn't find hand."), joker_rule)
+        let hand = Hand::from_str(split.next().expect("Couldn't find hand."), rules)
             .expect("Couldn't create Hand object from string.");
         let bid = split.next().expect("Couldn't find bid.")
-            .parse::<i32>().expect("Couldn't parse bid.");
-        hands[hand.type_value as usize].push((hand, bid));
+            .parse::<u64>().expect("Couldn't parse bid.");
+        hands.push((hand, bid));
     }
-    let mut lower_ranked = 0;
-    let mut total = 0;
-    for i in 0..hands.len() {
-        hands[i].sort_by_key(|t| t.0.card_values);
-        for j in 0..hands[i].len() {
-            let (_, bid) = hands[i][j];
-            let rank = lower_ranked + j + 1;
-            total += bid * (rank as i32);
-        }
-        lower_ranked += hands[i].len();
+    let ranked = rank_hands(&hands);
+    if opts.flag("ties") {
+        let tied = ranked.iter().filter(|r| r.tied).count();
+        println!("{tied} hands are tied with an identical hand.");
     }
-    total.to_string()
+    if answer::big_mode(opts) {
+        answer::sum(ranked.iter().map(|r| BigInt::from(r.bid) * r.rank), opts)
+    } else {
+        let winnings = ranked.iter()
+            .map(|r| r.winnings.expect("Winnings overflowed; try the `big` option."));
+        answer::sum(winnings, opts)
+    }
+}
+
+pub(crate) fn part_1(s: &str, opts: &Options) -> Answer {
+    process_hands(s, &Rules::standard(), opts)
 }
 
-pub(crate) fn part_1(s: &str) -> String {
-    process_hands(s, false)
+pub(crate) fn part_2(s: &str, opts: &Options) -> Answer {
+    process_hands(s, &Rules::jokers(), opts)
 }
 
-pub(crate) fn part_2(s: &str) -> String {
-    process_hands(s, true)
-}
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIVE_CARD_TYPES: [&[u32]; 7] = [
+        &[1, 1, 1, 1, 1],  // High card
+        &[2, 1, 1, 1],  // One pair
+        &[2, 2, 1],  // Two pair
+        &[3, 1, 1],  // Three of a kind
+        &[3, 2],  // Full house
+        &[4, 1],  // Four of a kind
+        &[5]  // Five of a kind
+    ];
+
+    /// Build a hand whose count signature is `pattern`, using the given cards in turn.
+    fn hand_with_pattern(pattern: &[u32], cards: &[char]) -> Vec<char> {
+        pattern.iter()
+            .zip(cards)
+            .flat_map(|(n, c)| std::iter::repeat_n(*c, *n as usize))
+            .collect()
+    }
+
+    /// Classify a hand under wildcard rules by trying each of the candidate cards in place of each
+    /// wildcard and keeping the strongest result.
+    fn brute_force_classify(rules: &Rules, cards: &[char], candidates: &[char]) -> usize {
+        match cards.iter().position(|c| rules.wildcards.contains(c)) {
+            None => rules.classify(cards).unwrap(),
+            Some(i) => candidates.iter()
+                .map(|c| {
+                    let mut substituted = cards.to_vec();
+                    substituted[i] = *c;
+                    brute_force_classify(rules, &substituted, candidates)
+                })
+                .max()
+                .unwrap()
+        }
+    }
+
+    #[test]
+    fn five_card_partitions_are_ordered_by_strength() {
+        let expected: Vec<Vec<u32>> = FIVE_CARD_TYPES.iter().map(|t| t.to_vec()).collect();
+        assert_eq!(partitions(5), expected);
+    }
+
+    #[test]
+    fn classifies_every_five_card_pattern() {
+        let rules = Rules::standard();
+        for (type_value, pattern) in FIVE_CARD_TYPES.iter().enumerate() {
+            let cards = hand_with_pattern(pattern, &['A', 'K', 'Q', 'J', 'T']);
+            assert_eq!(rules.classify(&cards), Ok(type_value), "{cards:?}");
+        }
+    }
+
+    #[test]
+    fn jokers_make_every_pattern_strongest() {
+        let rules = Rules::jokers();
+        // Every pattern, with every number of jokers, appears among the hands drawn from six cards.
+        // Substituting a card that isn't in the hand is as good as substituting any other, so the
+        // five non-jokers are the only substitutes worth trying.
+        let alphabet = ['J', '2', '3', '4', '5', '6'];
+        for n in 0..alphabet.len().pow(5) {
+            let cards: Vec<char> = (0..5)
+                .map(|i| alphabet[n / alphabet.len().pow(i) % alphabet.len()])
+                .collect();
+            assert_eq!(
+                rules.classify(&cards).unwrap(),
+                brute_force_classify(&rules, &cards, &alphabet[1..]),
+                "{cards:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn multiple_wildcards_and_other_hand_sizes() {
+        let rules = Rules::new("*?23456789", "*?", 3);
+        assert_eq!(rules.hand_types, vec!(vec!(1, 1, 1), vec!(2, 1), vec!(3)));
+        assert_eq!(rules.classify(&['*', '?', '2']), Ok(2));
+        assert_eq!(rules.classify(&['*', '3', '2']), Ok(1));
+        assert!(Hand::from_str("2345", &rules).is_err());
+    }
+
+    #[test]
+    fn identical_hands_share_a_rank() {
+        let rules = Rules::standard();
+        let hands: Vec<(Hand, u64)> = [("32T3K", 1), ("KK677", 2), ("32T3K", 3), ("AAAAA", 4)]
+            .iter()
+            .map(|(h, b)| (Hand::from_str(h, &rules).unwrap(), *b))
+            .collect();
+        let ranked = rank_hands(&hands);
+        let ranks: Vec<(usize, bool)> = ranked.iter().map(|r| (r.rank, r.tied)).collect();
+        assert_eq!(ranks, vec!((1, true), (3, false), (1, true), (4, false)));
+        assert_eq!(ranked[3].winnings, Some(16));
+    }
+}
@@ -1,98 +1,187 @@
-use std::collections::HashMap;
-use std::str::{FromStr, Lines};
-use num::integer::lcm;
+use std::collections::HashSet;
+use std::str::FromStr;
+use crate::cycles::{analyse, first_common_hit, HitPattern};
+use crate::graph::Graph;
+use crate::answer::Answer;
+use crate::options::Options;
 
+/// A node and its children, parsed from a line of the form `AAA = (BBB, CCC)`. Labels may be any
+/// length and a node may have any number of children.
 #[derive(Clone, Debug)]
 struct Node {
     label: String,
-    left: String,
-    right: String
+    children: Vec<String>
 }
 
-impl<'a> FromStr for Node {
+/// Check that a label is non-empty and contains no characters that are part of the node grammar.
+fn parse_label(s: &str) -> Result<String, String> {
+    let label = s.trim();
+    if label.is_empty() || label.contains(|c: char| c.is_whitespace() || "=(),".contains(c)) {
+        return Err(format!("Invalid node label `{label}`."))
+    }
+    Ok(String::from(label))
+}
+
+impl FromStr for Node {
     type Err = String;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let label = String::from(&s[0..3]);
-        let left = String::from(&s[7..10]);
-        let right = String::from(&s[12..15]);
-        Ok(Node { label, left, right })
+        let (label_s, children_s) = s.split_once('=')
+            .ok_or(format!("Could not find `=` in node `{s}`."))?;
+        let children_s = children_s.trim()
+            .strip_prefix('(')
+            .and_then(|c| c.strip_suffix(')'))
+            .ok_or(format!("Children of node `{s}` are not enclosed in parentheses."))?;
+        let label = parse_label(label_s)?;
+        let children = children_s.split(',')
+            .map(parse_label)
+            .collect::<Result<Vec<String>, String>>()?;
+        Ok(Node { label, children })
     }
 }
 
-fn get_nodes(lines: Lines) -> (HashMap<String, usize>, Vec<Node>) {
-    let mut node_map: HashMap<String, usize> = HashMap::new();
-    let mut node_vec: Vec<Node> = vec!();
-    for (node_i, line) in lines.enumerate() {
-        let n = Node::from_str(line)
-            .expect(&format!("Error parsing Node from line `{line}`."));
-        let n_label = n.label.clone();
-        node_vec.push(n);
-        node_map.insert(n_label, node_i);
-    }
-    (node_map, node_vec)
+/// The instructions and the network of nodes they are followed through. The i-th character of
+/// the instruction alphabet selects a node's i-th child.
+struct Network {
+    instructions: Vec<char>,
+    graph: Graph<char>
 }
 
-fn get_steps(
-    instructions: &str,
-    start_node: &Node,
-    node_map: &HashMap<String, usize>,
-    node_vec: &[Node],
-    end_fn: fn(&str) -> bool
-) -> i64 {
-    let mut current_node = start_node;
-    let mut steps = 0;
-    loop {
-        for c in instructions.chars() {
-            steps += 1;
-            let label = match c {
-                'L' => current_node.left.clone(),
-                'R' => current_node.right.clone(),
-                _ => panic!("Invalid instruction.")
-            };
-            if end_fn(&label) {
-                return steps;
-            } else {
-                current_node = &node_vec[node_map[&label]];
+impl Network {
+
+    fn parse(s: &str, alphabet: &[char]) -> Result<Network, String> {
+        let mut lines = s.lines();
+        let instructions: Vec<char> = lines.next().ok_or("Could not find instruction line.")?
+            .trim().chars().collect();
+        if instructions.is_empty() {
+            return Err(String::from("Instruction line is empty."))
+        }
+        if let Some(c) = instructions.iter().find(|c| !alphabet.contains(c)) {
+            return Err(format!("Instruction `{c}` is not in the alphabet {alphabet:?}."))
+        }
+        let mut graph: Graph<char> = Graph::new();
+        let mut defined: HashSet<String> = HashSet::new();
+        for line in lines.filter(|l| !l.trim().is_empty()) {
+            let n = Node::from_str(line)?;
+            if !defined.insert(n.label.clone()) {
+                return Err(format!("Node {} is defined more than once.", n.label))
+            }
+            if n.children.len() > alphabet.len() {
+                return Err(format!(
+                    "Node {} has {} children but the alphabet only has {} instructions.",
+                    n.label, n.children.len(), alphabet.len()
+                ))
+            }
+            let id = graph.add_node(&n.label);
+            for (instr, child) in alphabet.iter().zip(n.children.iter()) {
+                let child_id = graph.add_node(child);
+                graph.add_edge(id, *instr, child_id);
             }
         }
+        Ok(Network { instructions, graph })
+    }
+
+    /// Analyse the cycle followed from the given node, with the state at each step being the
+    /// current node and the position in the instructions.
+    fn analyse_path(&self, start_node: u32, end_fn: &dyn Fn(&str) -> bool) -> HitPattern {
+        let step = |(node, instr_i): &(u32, usize)| {
+            let instr = self.instructions[*instr_i];
+            let next = self.graph.successor(*node, &instr).unwrap_or_else(|| panic!(
+                "Node {} has no child for instruction {instr}.", self.graph.label(*node)
+            ));
+            (next, (instr_i + 1) % self.instructions.len())
+        };
+        analyse(&(start_node, 0), step, |(node, _)| end_fn(self.graph.label(*node)))
     }
+}
 
+/// Parse the network using the instruction alphabet given by the `alphabet` option (`LR` by
+/// default).
+fn get_network(s: &str, opts: &Options) -> Network {
+    let alphabet: Vec<char> = opts.get("alphabet").unwrap_or("LR").chars().collect();
+    Network::parse(s, &alphabet).expect("Could not parse network.")
 }
 
-pub(crate) fn part_1(s: &str) -> String {
-    let mut lines = s.lines();
-    let instructions = lines.next().expect("Could not find instruction line.");
-    lines.next();
-    let (node_map, node_vec) = get_nodes(lines);
-    get_steps(
-        instructions,
-        &node_vec[node_map["AAA"]],
-        &node_map,
-        &node_vec,
-        |s| s == "ZZZ"
-    ).to_string()
+/// Find the number of steps from the node labelled with the `start` option (`AAA` by default) to
+/// the node labelled with the `end` option (`ZZZ` by default).
+pub(crate) fn part_1(s: &str, opts: &Options) -> Answer {
+    let network = get_network(s, opts);
+    let start = opts.get("start").unwrap_or("AAA");
+    let end = opts.get("end").unwrap_or("ZZZ");
+    network.analyse_path(
+        network.graph.id(start).unwrap_or_else(|| panic!("Could not find node {start}.")),
+        &|s| s == end
+    ).first_hit().unwrap_or_else(|| panic!("Never reached {end}.")).into()
 }
 
-pub(crate) fn part_2(s: &str) -> String {
-    let mut lines = s.lines();
-    let instructions = lines.next().expect("Could not find instruction line.");
-    lines.next();
-    let (node_map, node_vec) = get_nodes(lines);
-    let mut current_nodes: Vec<&Node> = vec!();
-    for n in node_vec.iter() {
-        if n.label.ends_with('A') {
-            current_nodes.push(n);
-        }
+/// Find the number of steps until every path starting at a node whose label ends with the `start`
+/// option (`A` by default) is at a node whose label ends with the `end` option (`Z` by default).
+pub(crate) fn part_2(s: &str, opts: &Options) -> Answer {
+    let network = get_network(s, opts);
+    let start = opts.get("start").unwrap_or("A");
+    let end = opts.get("end").unwrap_or("Z");
+    let patterns: Vec<HitPattern> = network.graph.ids()
+        .filter(|id| network.graph.label(*id).ends_with(start))
+        .map(|id| network.analyse_path(id, &|s| s.ends_with(end)))
+        .collect();
+    first_common_hit(&patterns)
+        .unwrap_or_else(|e| panic!("{e}"))
+        .expect("Ghosts are never all on end nodes at the same time.")
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The first ghost reaches 1Z after 1 step and then every 3 steps. The second takes 3 steps to
+    /// reach 2Z and then stays there. The lcm of the first arrivals, 3, is wrong because of the
+    /// second ghost's tail: the first ghost is at 1C then.
+    const TAILED: &str = "\
+L
+
+1A = (1Z, 1Z)
+1Z = (1B, 1B)
+1B = (1C, 1C)
+1C = (1Z, 1Z)
+2A = (2X, 2X)
+2X = (2Y, 2Y)
+2Y = (2Z, 2Z)
+2Z = (2Z, 2Z)
+";
+
+    #[test]
+    fn allows_for_tails_before_cycles() {
+        assert_eq!(part_2(TAILED, &Options::default()), Answer::from(4u64));
+    }
+
+    fn options(args: &[&str]) -> Options {
+        Options::from_args(args.iter().map(|a| String::from(*a)))
     }
-    current_nodes.iter()
-        .map(|&n| get_steps(
-            instructions,
-            n,
-            &node_map,
-            &node_vec,
-            |s| s.ends_with('Z')
-        ))
-        .fold(1, lcm)
-        .to_string()
-}
\ No newline at end of file
+
+    #[test]
+    fn parses_any_labels_and_alphabets() {
+        // Three children chosen by `a`, `b` and `c`, with labels of different lengths.
+        let input = "bc\n\nstart = (x, long, start)\nx = (x, x, x)\nlong = (x, x, end)\n\
+                     end = (end, end, end)\n";
+        let opts = options(&["alphabet=abc", "start=start", "end=end"]);
+        assert_eq!(part_1(input, &opts), Answer::from(2u64));
+        assert!(Network::parse(input, &['L', 'R']).is_err());
+
+        // Ghosts start at labels ending in `s` and stop at labels ending in `t`.
+        let input = "L\n\n1s = (1t, 1t)\n1t = (1s, 1s)\n22s = (22t, 22t)\n22t = (22t, 22t)\n";
+        assert_eq!(part_2(input, &options(&["start=s", "end=t"])), Answer::from(1u64));
+    }
+
+    #[test]
+    fn rejects_invalid_networks() {
+        let error = |s| Network::parse(s, &['L', 'R']).err();
+        assert_eq!(error("\n\nAAA = (AAA, AAA)\n"),
+                   Some(String::from("Instruction line is empty.")));
+        assert_eq!(error("L\n\nAAA = (BBB, BBB)\nAAA = (AAA, AAA)\n"),
+                   Some(String::from("Node AAA is defined more than once.")));
+        assert!(error("L\n\nAAA = (BBB, CCC, DDD)\n").is_some());
+        assert!(error("L\n\nA A = (B, C)\n").is_some());
+        assert!(error("X\n\nAAA = (AAA, AAA)\n").is_some());
+    }
+}
@@ -1,50 +1,36 @@
-use std::ops::{Sub, Add};
-
+use std::str::FromStr;
+use num::{BigInt, CheckedAdd, CheckedSub, Zero};
 use crate::common::parse_on_whitespace;
-
-fn all_same<T>(seq: &Vec<T>) -> bool where T: Ord {
-    for i in 1..seq.len() {
-        if seq[i] != seq[i-1] {
-            return false
-        }
-    }
-    true
-}
-
-fn get_diffs<T>(seq: &Vec<T>) -> Vec<T> where T: Sub<Output=T> + Copy {
-    let mut diffs: Vec<T> = vec!();
-    for i in 1..seq.len() {
-        diffs.push(seq[i] - seq[i-1]);
-    }
-    diffs
+use crate::sequence::{extrapolate_backward, extrapolate_forward};
+use crate::answer::{self, Answer};
+use crate::options::Options;
+
+/// Extrapolate every line's sequence with `f` and sum the results.
+fn sum_extrapolated<T, F>(s: &str, f: F, opts: &Options) -> Answer
+    where T: FromStr + CheckedAdd + CheckedSub + Zero + Clone + Into<BigInt> + Into<Answer>,
+          F: Fn(&[T], usize) -> Option<T> {
+    let values = s.lines().map(|line| {
+        let seq = parse_on_whitespace::<T>(line)
+            .unwrap_or_else(|_| panic!("Could not parse sequence `{line}`."));
+        f(&seq, 1).expect("Extrapolation overflowed; try the `big` option.")
+    });
+    answer::sum(values, opts)
 }
 
-
-fn extrapolate<T>(seq: &Vec<T>) -> T where T: Sub<Output = T> + Add<Output = T> + Ord + Copy {
-    let last = *seq.last().expect("Could not find last element of sequence.");
-    if all_same(seq) {
-        last
+/// Sum the next values of the sequences, using i64 unless the `big` option is set.
+pub(crate) fn part_1(s: &str, opts: &Options) -> Answer {
+    if answer::big_mode(opts) {
+        sum_extrapolated::<BigInt, _>(s, extrapolate_forward, opts)
     } else {
-        let diffs = get_diffs(seq);
-        last + extrapolate(&diffs)
+        sum_extrapolated::<i64, _>(s, extrapolate_forward, opts)
     }
 }
 
-pub(crate) fn part_1(s: &str) -> String {
-    let mut total: i64 = 0;
-    for line in s.lines() {
-        total += extrapolate(&parse_on_whitespace::<i64>(line).unwrap())
-
+/// Sum the previous values of the sequences, using i64 unless the `big` option is set.
+pub(crate) fn part_2(s: &str, opts: &Options) -> Answer {
+    if answer::big_mode(opts) {
+        sum_extrapolated::<BigInt, _>(s, extrapolate_backward, opts)
+    } else {
+        sum_extrapolated::<i64, _>(s, extrapolate_backward, opts)
     }
-    total.to_string()
 }
-
-pub(crate) fn part_2(s: &str) -> String {
-    let mut total: i64 = 0;
-    for line in s.lines() {
-        let mut seq = parse_on_whitespace::<i64>(line).unwrap();
-        seq.reverse();
-        total += extrapolate(&seq);
-    }
-    total.to_string()
-}
\ No newline at end of file
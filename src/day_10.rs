@@ -1,118 +1,273 @@
-use std::collections::HashSet;
 use std::str::FromStr;
-use geo::{Contains, coord, Polygon};
-use geo::geometry::LineString;
 use crate::common::Grid;
+use crate::answer::Answer;
+use crate::options::Options;
 
 static N: (i32, i32) = (-1, 0);
 static W: (i32, i32) = (0, -1);
 static E: (i32, i32) = (0, 1);
 static S: (i32, i32) = (1, 0);
 
+/// The pipes that can appear in the maze and the directions each one connects.
+static PIPES: [(char, [(i32, i32); 2]); 6] = [
+    ('|', [N, S]),
+    ('-', [E, W]),
+    ('L', [N, E]),
+    ('J', [N, W]),
+    ('7', [S, W]),
+    ('F', [S, E])
+];
 
-/// Get the tiles that the pipe at the given position connect to. Doesn't verify that the locations
-/// are valid.
-fn get_connections(
-    grid: &Grid<char>,
-    posn: &(usize, usize),
-    c: &char
-)-> Option<[Option<(usize, usize)>; 2]> {
-    let directions = match c {
-        '|' => Some((N, S)),
-        '-' => Some((E, W)),
-        'L' => Some((N, E)),
-        'J' => Some((N, W)),
-        '7' => Some((S, W)),
-        'F' => Some((S, E)),
-        '.' => None,
-        _ => panic!("Invalid pipe: {c}")
-    };
-    directions.map(|d| [grid.apply_offset(&posn, &d.0), grid.apply_offset(&posn, &d.1)])
+/// Get the directions that the given tile connects to, or None if the tile is not a pipe.
+fn get_directions(c: char) -> Result<Option<[(i32, i32); 2]>, String> {
+    match c {
+        '.' => Ok(None),
+        _ => match PIPES.iter().find(|(p, _)| *p == c) {
+            Some((_, dirs)) => Ok(Some(*dirs)),
+            None => Err(format!("Invalid pipe: {c}"))
+        }
+    }
 }
 
-fn get_next_posn(grid: &Grid<char>, from: &(usize, usize), posn: &(usize, usize), c: &char) -> Option<(usize, usize)> {
-    let conns = get_connections(&grid, posn, c);
-    match conns {
-        Some(c) => {
-            let c1 = c[0];
-            let c2 = c[1];
-            if c1 == Some(*from) {
-                c2
-            } else if c2 == Some(*from) {
-                c1
-            } else {
-                None
+#[derive(Clone, Debug)]
+struct PipeMaze {
+    grid: Grid<char>,
+    start: (usize, usize),
+    /// The pipe hidden under the `S` tile, inferred from the pipes around it.
+    start_pipe: char
+}
+
+impl FromStr for PipeMaze {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim().is_empty() {
+            return Err(String::from("Maze is empty."))
+        }
+        let grid = Grid::from_str(s).map_err(|_| String::from("Could not create Grid."))?;
+        let mut starts = 0;
+        for p in grid.iter_positions() {
+            match grid.get(&p) {
+                Some('S') => starts += 1,
+                Some(c) => { get_directions(c)?; },
+                None => {}
             }
-        },
-        None => None
+        }
+        if starts > 1 {
+            return Err(String::from("Found more than one 'S'."))
+        }
+        let start = grid.find(&'S').ok_or("Could not find 'S' in grid.")?;
+
+        // The start pipe connects in exactly those directions whose neighbouring pipe connects
+        // back to the start.
+        let connected: Vec<(i32, i32)> = [N, E, S, W].into_iter()
+            .filter(|d| grid.apply_offset(&start, d)
+                .and_then(|p| get_directions(grid.get(&p)?).ok()?)
+                .is_some_and(|dirs| dirs.contains(&(-d.0, -d.1))))
+            .collect();
+        let start_pipe = match connected[..] {
+            [a, b] => PIPES.iter()
+                .find(|(_, dirs)| dirs.contains(&a) && dirs.contains(&b))
+                .map(|(p, _)| *p)
+                .expect("Every pair of directions should match a pipe."),
+            [] | [_] => return Err(format!(
+                "Start at {start:?} connects to {} pipe(s), so it is a dead end.", connected.len()
+            )),
+            _ => return Err(format!(
+                "Start at {start:?} connects to {} pipes, so the loop branches.", connected.len()
+            ))
+        };
+        Ok(PipeMaze { grid, start, start_pipe })
     }
 }
 
-fn find_path(grid: &Grid<char>) -> (i32, Vec<(usize, usize)>) {
-    let s_posn = grid.find(&'S').expect("Could not find 'S' in grid.");
-    let mut current = grid.neighbors(&s_posn, false);
-    let mut prev: Vec<Option<(usize, usize)>> = vec!();
-    let mut next: Vec<Option<(usize, usize)>> = vec!();
-    let mut history: Vec<Vec<(usize, usize)>> = vec!();
-    for _ in 0..current.len() {
-        prev.push(Some(s_posn));
-        next.push(None);
-        history.push(vec!(s_posn));
+impl PipeMaze {
+
+    /// The pipe at the given position, with `S` replaced by the start pipe.
+    fn tile(&self, posn: &(usize, usize)) -> Option<char> {
+        if *posn == self.start {
+            Some(self.start_pipe)
+        } else {
+            self.grid.get(posn)
+        }
+    }
+
+    /// Follow the loop from the start and return every position on it in order, beginning with
+    /// the start. Returns an error if the loop leads off the grid or into a pipe that doesn't
+    /// connect back.
+    fn find_loop(&self) -> Result<Vec<(usize, usize)>, String> {
+        let mut path: Vec<(usize, usize)> = vec!(self.start);
+        let mut posn = self.start;
+        let mut came_from: Option<(i32, i32)> = None;
+        loop {
+            let c = self.tile(&posn).ok_or(format!("Position {posn:?} is off the grid."))?;
+            let dirs = get_directions(c)?
+                .ok_or(format!("Loop leads to {posn:?}, which is not a pipe."))?;
+            // Leave by whichever of the pipe's connections we didn't arrive through.
+            let dir = match came_from {
+                Some(from) if dirs[0] == from => dirs[1],
+                Some(from) if dirs[1] == from => dirs[0],
+                Some(_) => return Err(format!(
+                    "Pipe {c} at {posn:?} does not connect back to the previous pipe."
+                )),
+                None => dirs[0]
+            };
+            posn = self.grid.apply_offset(&posn, &dir)
+                .ok_or(format!("Pipe {c} at {posn:?} leads off the grid."))?;
+            if posn == self.start {
+                return Ok(path)
+            }
+            path.push(posn);
+            came_from = Some((-dir.0, -dir.1));
+        }
     }
-    let mut steps = 1;
-    loop {
-        for (i, c_posn) in current.iter().enumerate() {
-            match c_posn {
-                Some(p) => {
-                    history[i].push(*p);
-                    let c = grid.get(p)
-                        .unwrap_or_else( | | panic!("Could not find character at {p:?}"));
-                    let n_posn = get_next_posn(&grid, &prev[i].unwrap(), p, &c);
-                    //println!("n_posn is {n_posn:?}");
-                    if let Some(some_n_posn) = n_posn {
-                        let other_path_i = next.iter().position(|p| p == &n_posn);
-                        if let Some(j) = other_path_i {
-                            let mut full_path = history[i].clone();
-                            full_path.push(some_n_posn);
-                            let other_path = &history[j];
-                            for p in other_path.iter().rev() {
-                                full_path.push(*p);
-                            }
-                            full_path.push(s_posn);
-                            return (steps + 1, full_path.clone())
-
-                        }
+
+    /// Return the tiles enclosed by the loop, in row-major order.
+    ///
+    /// Scans each row from left to right, counting how many times we cross the loop. A tile not
+    /// on the loop is inside it if we have crossed an odd number of times. A `|` is a crossing,
+    /// as is a horizontal run of the loop that enters and leaves on opposite sides (`F-J` or
+    /// `L-7`). A run that enters and leaves on the same side (`F-7` or `L-J`) just touches the
+    /// loop, so is not.
+    fn interior(&self, path: &[(usize, usize)]) -> Vec<(usize, usize)> {
+        let (n_rows, n_cols) = self.grid.shape();
+        let mut on_loop = vec!(vec!(false; n_cols); n_rows);
+        for (r, c) in path {
+            on_loop[*r][*c] = true;
+        }
+        let mut inside_tiles: Vec<(usize, usize)> = vec!();
+        for (r, row) in on_loop.iter().enumerate() {
+            let mut inside = false;
+            // The corner that started the horizontal run we are currently in, if any.
+            let mut run_start: Option<char> = None;
+            for (c, is_loop) in row.iter().enumerate() {
+                if !is_loop {
+                    if inside {
+                        inside_tiles.push((r, c));
                     }
-                    next[i] = n_posn;
-                },
-                None => next[i] = None
+                    continue
+                }
+                match (self.tile(&(r, c)), run_start) {
+                    (Some('|'), _) => inside = !inside,
+                    (Some(corner @ ('F' | 'L')), _) => run_start = Some(corner),
+                    (Some('J'), Some('F')) | (Some('7'), Some('L')) => {
+                        inside = !inside;
+                        run_start = None;
+                    },
+                    (Some('J' | '7'), _) => run_start = None,
+                    _ => {}
+                }
             }
         }
-        steps += 1;
-        prev = current.clone();
-        current = next.clone();
+        inside_tiles
     }
 }
-pub(crate) fn part_1(s: &str) -> String {
-    let grid = Grid::from_str(s).expect("Could not create Grid.");
-    let (steps, _) = find_path(&grid);
-    steps.to_string()
+
+pub(crate) fn part_1(s: &str, _opts: &Options) -> Answer {
+    let maze = PipeMaze::from_str(s).expect("Could not parse maze.");
+    let path = maze.find_loop().expect("Could not find loop.");
+    (path.len() / 2).into()
 }
 
-pub(crate) fn part_2(s: &str) -> String {
-    let grid = Grid::from_str(s).expect("Could not create Grid.");
-    let (_, path) = find_path(&grid);
-    let path_set: HashSet<&(usize, usize)> = HashSet::from_iter(&path);
-    let linestr: LineString<f32> = path.iter()
-        .map(|p| coord! { x: p.1 as f32, y: p.0 as f32 })
-        .collect();
-    let poly: Polygon<f32> = Polygon::new(linestr, vec!());
-    let mut in_loop = 0;
-    for p in grid.iter_positions() {
-        if path_set.contains(&p) {
-        } else if poly.contains(&coord! {x: p.1 as f32, y: p.0 as f32}) {
-            in_loop += 1;
-        }
+pub(crate) fn part_2(s: &str, _opts: &Options) -> Answer {
+    let maze = PipeMaze::from_str(s).expect("Could not parse maze.");
+    let path = maze.find_loop().expect("Could not find loop.");
+    maze.interior(&path).len().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SQUARE: &str = "\
+-L|F7
+7S-7|
+L|7||
+-L-J|
+L|-JF
+";
+
+    const COMPLEX: &str = "\
+7-F7-
+.FJ|7
+SJLL7
+|F--J
+LJ.LJ
+";
+
+    fn error(s: &str) -> String {
+        PipeMaze::from_str(s).and_then(|m| m.find_loop()).unwrap_err()
+    }
+
+    #[test]
+    fn infers_the_start_pipe() {
+        let square = PipeMaze::from_str(SQUARE).unwrap();
+        assert_eq!((square.start, square.start_pipe), ((1, 1), 'F'));
+        let complex = PipeMaze::from_str(COMPLEX).unwrap();
+        assert_eq!((complex.start, complex.start_pipe), ((2, 0), 'F'));
+        assert_eq!(part_1(COMPLEX, &Options::default()), Answer::from(8));
+    }
+
+    #[test]
+    fn follows_the_loop_in_order() {
+        let square = PipeMaze::from_str(SQUARE).unwrap();
+        assert_eq!(square.find_loop(), Ok(vec!(
+            (1, 1), (2, 1), (3, 1), (3, 2), (3, 3), (2, 3), (1, 3), (1, 2)
+        )));
     }
-    in_loop.to_string()
-}
\ No newline at end of file
+
+    #[test]
+    fn rejects_broken_mazes() {
+        assert_eq!(error(".....\n.S-..\n.|...\n.....\n"),
+                   "Loop leads to (3, 1), which is not a pipe.");
+        assert_eq!(error(".|.\n-S-\n...\n"),
+                   "Start at (1, 1) connects to 3 pipes, so the loop branches.");
+        assert_eq!(error("S-X\n"), "Invalid pipe: X");
+        assert_eq!(error(".S.\n"), "Start at (0, 1) connects to 0 pipe(s), so it is a dead end.");
+        assert_eq!(error("S.\n.S\n"), "Found more than one 'S'.");
+        assert_eq!(error("..\n"), "Could not find 'S' in grid.");
+        assert_eq!(error(""), "Maze is empty.");
+    }
+
+    /// Interior tiles can be separated from the outside by pipes that touch without a gap.
+    const SQUEEZED: &str = "\
+..........
+.S------7.
+.|F----7|.
+.||....||.
+.||....||.
+.|L-7F-J|.
+.|..||..|.
+.L--JL--J.
+..........
+";
+
+    const JUNK: &str = "\
+FF7FSF7F7F7F7F7F---7
+L|LJ||||||||||||F--J
+FL-7LJLJ||||||LJL-77
+F--JF--7||LJLJ7F7FJ-
+L---JF-JLJ.||-FJLJJ7
+|F|F-JF---7F7-L7L|7|
+|FFJF7L7F-JF7|JL---7
+7-L-JL7||F7|L7F-7F7|
+L.L7LFJ|||||FJL7||LJ
+L7JLJL-JLJLJL--JLJ.L
+";
+
+    fn interior(s: &str) -> Vec<(usize, usize)> {
+        let maze = PipeMaze::from_str(s).unwrap();
+        maze.interior(&maze.find_loop().unwrap())
+    }
+
+    #[test]
+    fn finds_tiles_inside_the_loop() {
+        assert_eq!(interior(SQUEEZED), vec!((6, 2), (6, 3), (6, 6), (6, 7)));
+        assert_eq!(part_2(SQUEEZED, &Options::default()), Answer::from(4));
+        assert_eq!(interior(JUNK), vec!(
+            (3, 14), (4, 10), (4, 11), (4, 12), (4, 13), (5, 11), (5, 12), (5, 13), (6, 13),
+            (6, 14)
+        ));
+        assert_eq!(part_2(JUNK, &Options::default()), Answer::from(10));
+    }
+}
@@ -1,86 +1,233 @@
-use std::cmp::{max, min};
-use std::collections::HashSet;
 use std::str::FromStr;
-use num::abs;
 use crate::common::Grid;
+use crate::answer::Answer;
+use crate::options::Options;
 
-/// Return a tuple of (empty rows, empty columns)
-fn get_empties(grid: &Grid<char>) -> (HashSet<usize>, HashSet<usize>) {
-    let mut empty_rows: HashSet<usize> = HashSet::new();
-    let mut empty_cols: HashSet<usize> = HashSet::new();
+/// The largest expanded universe, in tiles, that we are willing to render.
+const MAX_RENDER_TILES: u128 = 10_000_000;
 
-    let (rows, cols) = grid.shape();
-    for i in 0..rows {
-        if grid.find_col(i, &'#').is_none() {
-            empty_rows.insert(i);
-        }
-    }
-    for i in 0..cols {
-        if grid.find_row(i, &'#').is_none() {
-            empty_cols.insert(i);
-        }
+/// For each index along an axis, return the coordinate it has after expansion: its original
+/// index plus `expansion_size - 1` for each empty line before it (as each empty line is
+/// *replaced* by `expansion_size` lines). The last element is the expanded length of the axis.
+fn expanded_coords(is_empty: &[bool], expansion_size: u64) -> Vec<u128> {
+    let mut coords: Vec<u128> = vec!();
+    let mut coord: u128 = 0;
+    for empty in is_empty {
+        coords.push(coord);
+        coord += if *empty { expansion_size as u128 } else { 1 };
     }
-    (empty_rows, empty_cols)
+    coords.push(coord);
+    coords
 }
 
-fn get_galaxies(grid: &Grid<char>) -> Vec<(usize, usize)> {
-    let mut positions: Vec<(usize, usize)> = vec!();
-    for p in grid.iter_positions() {
-        let c = grid.get(&p).unwrap();
-        if c == '#' {
-            positions.push(p);
-        }
+/// Return the sum of the absolute differences between every pair of values. After sorting, the
+/// k-th value is at least as large as each of the k values before it, so contributes
+/// `k * x - (sum of the values before it)` to the sum. Returns None on overflow.
+fn sum_pairwise_diffs(mut values: Vec<u128>) -> Option<u128> {
+    values.sort();
+    let mut total: u128 = 0;
+    let mut sum_before: u128 = 0;
+    for (k, x) in values.iter().enumerate() {
+        total = total.checked_add((k as u128).checked_mul(*x)? - sum_before)?;
+        sum_before = sum_before.checked_add(*x)?;
     }
-    positions
+    Some(total)
 }
 
-fn get_pairs(positions: &Vec<(usize, usize)>) -> Vec<((usize, usize), (usize, usize))> {
-    let mut pairs: Vec<((usize, usize), (usize, usize))> = vec!();
-    for i in 0..positions.len() {
-        for j in i+1..positions.len() {
-            pairs.push((positions[i], positions[j]));
-        }
-    }
-    pairs
+/// The galaxies in an image, at their positions after the universe has expanded.
+#[derive(Clone, Debug)]
+struct Universe {
+    galaxies: Vec<(u128, u128)>,
+    /// The number of rows and columns in the expanded universe.
+    shape: (u128, u128)
 }
 
-fn get_distance(
-    p1: &(usize, usize), p2: &(usize, usize),
-    empty_rows: &HashSet<usize>, empty_cols: &HashSet<usize>,
-    expansion_size: &i32
-) -> i32 {
-    let mut row_diff = abs(p1.0 as i32 - p2.0 as i32);
-    for i in min(p1.0, p2.0)..max(p1.0, p2.0) {
-        if empty_rows.contains(&i) {
-            // subtract 1 to reflect that we are *replacing* the empty row with the new rows
-            row_diff += expansion_size - 1;
+impl Universe {
+
+    fn new(grid: &Grid<char>, expansion_size: u64) -> Universe {
+        let (rows, cols) = grid.shape();
+        let empty_rows: Vec<bool> = (0..rows).map(|i| grid.find_col(i, &'#').is_none()).collect();
+        let empty_cols: Vec<bool> = (0..cols).map(|i| grid.find_row(i, &'#').is_none()).collect();
+        let row_coords = expanded_coords(&empty_rows, expansion_size);
+        let col_coords = expanded_coords(&empty_cols, expansion_size);
+        let galaxies = grid.iter_positions()
+            .filter(|p| grid.get(p) == Some('#'))
+            .map(|(r, c)| (row_coords[r], col_coords[c]))
+            .collect();
+        Universe { galaxies, shape: (row_coords[rows], col_coords[cols]) }
+    }
+
+    /// The length of the shortest path between two galaxies, given by their indices.
+    fn distance(&self, a: usize, b: usize) -> u128 {
+        let (p1, p2) = (self.galaxies[a], self.galaxies[b]);
+        p1.0.abs_diff(p2.0) + p1.1.abs_diff(p2.1)
+    }
+
+    /// The sum of the distances between every pair of galaxies, or None if it overflows. As
+    /// distances are Manhattan distances, rows and columns can be summed separately.
+    fn total_distance(&self) -> Option<u128> {
+        sum_pairwise_diffs(self.galaxies.iter().map(|p| p.0).collect())?
+            .checked_add(sum_pairwise_diffs(self.galaxies.iter().map(|p| p.1).collect())?)
+    }
+
+    /// Return the `k` galaxies nearest to the given galaxy, as (index, distance) pairs sorted by
+    /// distance.
+    fn nearest(&self, galaxy: usize, k: usize) -> Vec<(usize, u128)> {
+        let mut others: Vec<(usize, u128)> = (0..self.galaxies.len())
+            .filter(|i| *i != galaxy)
+            .map(|i| (i, self.distance(galaxy, i)))
+            .collect();
+        if k < others.len() {
+            others.select_nth_unstable_by_key(k, |(_, d)| *d);
+            others.truncate(k);
         }
+        others.sort_by_key(|(_, d)| *d);
+        others
     }
-    let mut col_diff = abs(p1.1 as i32 - p2.1 as i32);
-    for i in min(p1.1, p2.1)..max(p1.1, p2.1) {
-        if empty_cols.contains(&i) {
-            col_diff += expansion_size - 1;
+
+    /// Draw the expanded universe in the same format as the input, or return an error if it is
+    /// too big to draw.
+    fn render(&self) -> Result<String, String> {
+        let (rows, cols) = self.shape;
+        if rows.saturating_mul(cols) > MAX_RENDER_TILES {
+            return Err(format!(
+                "Expanded universe is {rows} by {cols}, which is too big to render."
+            ))
         }
+        let mut lines = vec!(vec!('.'; cols as usize); rows as usize);
+        for (r, c) in &self.galaxies {
+            lines[*r as usize][*c as usize] = '#';
+        }
+        Ok(lines.iter().map(|l| l.iter().collect::<String>()).collect::<Vec<String>>().join("\n"))
+    }
+}
+
+/// Parse a galaxy number, counting from 1 as in the puzzle description, into an index.
+fn parse_galaxy(s: &str, universe: &Universe) -> usize {
+    match s.trim().parse::<usize>() {
+        Ok(n) if n >= 1 && n <= universe.galaxies.len() => n - 1,
+        _ => panic!("`{s}` is not a galaxy number between 1 and {}.", universe.galaxies.len())
     }
-    row_diff + col_diff
 }
 
-fn solve(s: &str, expansion_size: &i32) -> String {
+/// Return the sum of the distances between every pair of galaxies, with each empty row or column
+/// replaced by `factor` of them (the `factor` option, if given, overrides the default).
+///
+/// Alternatively, with the `pair` option (eg, `pair=5,9`) return the distance between those two
+/// galaxies; with the `nearest` option (eg, `nearest=5`) list the `k` (default 1) galaxies nearest
+/// to that galaxy; with the `coords` option list the expanded coordinates of every galaxy; or with
+/// the `render` option draw the expanded universe. Galaxies are numbered from 1.
+fn solve(s: &str, factor: u64, opts: &Options) -> Answer {
+    let factor = opts.get("factor")
+        .map_or(factor, |f| f.parse::<u64>().expect("Could not parse expansion factor."));
     let grid = Grid::from_str(s).expect("Could not create Grid.");
-    let (empty_rows, empty_cols) = get_empties(&grid);
-    let galaxies = get_galaxies(&grid);
-    let pairs = get_pairs(&galaxies);
-    let mut total: i64 = 0;
-    for (p1, p2) in pairs {
-        let d = get_distance(&p1, &p2, &empty_rows, &empty_cols, expansion_size);
-        total += d as i64;
-    }
-    total.to_string()
+    let universe = Universe::new(&grid, factor);
+    if let Some(pair) = opts.get("pair") {
+        let (a, b) = pair.split_once(',').expect("Pair should be in format `<galaxy>,<galaxy>`.");
+        return universe.distance(parse_galaxy(a, &universe), parse_galaxy(b, &universe)).into()
+    }
+    if let Some(galaxy) = opts.get("nearest") {
+        let k = opts.get("k").map_or(1, |k| k.parse::<usize>().expect("Could not parse k."));
+        return universe.nearest(parse_galaxy(galaxy, &universe), k).iter()
+            .map(|(i, d)| format!("Galaxy {}: {d}", i + 1))
+            .collect::<Vec<String>>()
+            .join("\n")
+            .into()
+    }
+    if opts.flag("coords") {
+        return universe.galaxies.iter()
+            .enumerate()
+            .map(|(i, (r, c))| format!("Galaxy {}: ({r}, {c})", i + 1))
+            .collect::<Vec<String>>()
+            .join("\n")
+            .into()
+    }
+    if opts.flag("render") {
+        return universe.render().unwrap_or_else(|e| panic!("{e}")).into()
+    }
+    universe.total_distance().expect("Total distance overflowed.").into()
 }
-pub(crate) fn part_1(s: &str) -> String {
-    solve(s, &2)
+
+pub(crate) fn part_1(s: &str, opts: &Options) -> Answer {
+    solve(s, 2, opts)
 }
 
-pub(crate) fn part_2(s: &str) -> String {
-    solve(s, &1000000)
-}
\ No newline at end of file
+pub(crate) fn part_2(s: &str, opts: &Options) -> Answer {
+    solve(s, 1000000, opts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "\
+...#......
+.......#..
+#.........
+..........
+......#...
+.#........
+.........#
+..........
+.......#..
+#...#.....
+";
+
+    fn universe(expansion_size: u64) -> Universe {
+        Universe::new(&Grid::from_str(EXAMPLE).unwrap(), expansion_size)
+    }
+
+    #[test]
+    fn sums_distances_like_brute_force() {
+        for factor in [1, 2, 10, 100, 1000000] {
+            let u = universe(factor);
+            let n = u.galaxies.len();
+            let brute: u128 = (0..n).flat_map(|a| (a + 1..n).map(move |b| (a, b)))
+                .map(|(a, b)| u.distance(a, b))
+                .sum();
+            assert_eq!(u.total_distance(), Some(brute), "factor {factor}");
+        }
+        assert_eq!(sum_pairwise_diffs(vec!()), Some(0));
+        assert_eq!(sum_pairwise_diffs(vec!(7, 1, 7, 3)), Some(6 + 4 + 6 + 2 + 4));
+        assert_eq!(sum_pairwise_diffs(vec!(0, u128::MAX, u128::MAX)), None);
+    }
+
+    #[test]
+    fn finds_the_nearest_galaxies() {
+        let u = universe(2);
+        assert_eq!(u.distance(4, 8), 9);
+        assert_eq!(u.nearest(4, 0), vec!());
+        assert_eq!(u.nearest(4, 3), vec!((2, 5), (7, 6), (3, 8)));
+        // Asking for more galaxies than there are returns all of them.
+        let all = u.nearest(4, 20);
+        assert_eq!(all, u.nearest(4, 8));
+        assert_eq!(all.len(), 8);
+        assert!(all.windows(2).all(|w| w[0].1 <= w[1].1));
+        assert_eq!(all.last(), Some(&(1, 13)));
+    }
+
+    fn options(args: &[&str]) -> Options {
+        Options::from_args(args.iter().map(|a| String::from(*a)))
+    }
+
+    #[test]
+    fn expands_empty_lines() {
+        assert_eq!(expanded_coords(&[false, true, false, true], 10), vec!(0, 1, 11, 12, 22));
+        assert_eq!(expanded_coords(&[], 10), vec!(0));
+        assert_eq!(part_1(EXAMPLE, &Options::default()), Answer::from(374));
+        assert_eq!(part_2(EXAMPLE, &options(&["factor=10"])), Answer::from(1030));
+        assert_eq!(part_2(EXAMPLE, &options(&["factor=100"])), Answer::from(8410));
+    }
+
+    #[test]
+    fn renders_the_expanded_universe() {
+        let u = Universe::new(&Grid::from_str("#..\n...\n..#\n").unwrap(), 2);
+        assert_eq!(u.shape, (4, 4));
+        assert_eq!(u.render(), Ok(String::from("#...\n....\n....\n...#")));
+
+        let huge = Universe::new(&Grid::from_str("#..\n...\n..#\n").unwrap(), 10000);
+        assert_eq!(huge.render(), Err(String::from(
+            "Expanded universe is 10002 by 10002, which is too big to render."
+        )));
+    }
+}
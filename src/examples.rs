@@ -0,0 +1,202 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::scaffold;
+
+/// The example input and expected answer found in one part's puzzle description.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Example {
+    pub(crate) input: Option<String>,
+    pub(crate) answer: Option<String>
+}
+
+/// Decode the HTML entities that appear in puzzle pages.
+fn decode_entities(s: &str) -> String {
+    s.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Remove every tag from an HTML fragment and decode its entities, leaving its text.
+fn text(html: &str) -> String {
+    let mut stripped = String::new();
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            c if !in_tag => stripped.push(c),
+            _ => {}
+        }
+    }
+    decode_entities(&stripped)
+}
+
+/// Return the contents of every element running from `open` to `close`, along with the position
+/// in `html` where each element starts and ends.
+fn elements<'a>(html: &'a str, open: &str, close: &str) -> Vec<(usize, usize, &'a str)> {
+    let mut found: Vec<(usize, usize, &str)> = vec!();
+    let mut from = 0;
+    while let Some(start) = html[from..].find(open).map(|i| from + i) {
+        let content_start = start + open.len();
+        let Some(end) = html[content_start..].find(close).map(|i| content_start + i) else {
+            break
+        };
+        from = end + close.len();
+        found.push((start, from, &html[content_start..end]));
+    }
+    found
+}
+
+/// Guess the example input in one part's description: the first code block introduced by text
+/// mentioning an example, or failing that the first code block.
+fn guess_input(article: &str) -> Option<String> {
+    let blocks = elements(article, "<pre><code>", "</code></pre>");
+    let mut previous_end = 0;
+    let mut introduced: Option<&str> = None;
+    for (start, end, block) in &blocks {
+        if text(&article[previous_end..*start]).to_lowercase().contains("example") {
+            introduced = Some(block);
+            break
+        }
+        previous_end = *end;
+    }
+    introduced.or(blocks.first().map(|(_, _, b)| *b)).map(text)
+}
+
+/// Guess the expected answer in one part's description: the last emphasised piece of code, which
+/// is how the puzzles highlight the answer to their examples.
+fn guess_answer(article: &str) -> Option<String> {
+    let mut emphasised = elements(article, "<code><em>", "</em></code>");
+    emphasised.extend(elements(article, "<em><code>", "</code></em>"));
+    emphasised.iter().max_by_key(|(start, _, _)| *start).map(|(_, _, answer)| text(answer))
+}
+
+/// Find the example for each part described in a saved puzzle page. A part that gives no example
+/// input of its own reuses the previous part's.
+pub(crate) fn parse_page(html: &str) -> Vec<Example> {
+    // Skip the rest of each article's opening tag, after its attributes.
+    let mut articles: Vec<&str> = elements(html, "<article", "</article>").iter()
+        .map(|(_, _, a)| a.split_once('>').map_or(*a, |(_, rest)| rest))
+        .collect();
+    if articles.is_empty() {
+        articles.push(html);
+    }
+    let mut parts: Vec<Example> = vec!();
+    for article in articles {
+        let input = guess_input(article)
+            .or_else(|| parts.last().and_then(|p| p.input.clone()));
+        parts.push(Example { input, answer: guess_answer(article) });
+    }
+    parts
+}
+
+/// Return a raw string literal containing `s`, with enough `#`s that `s` can't end it early.
+fn raw_literal(s: &str) -> String {
+    let mut hashes = String::from("#");
+    while s.contains(&format!("\"{hashes}")) {
+        hashes.push('#');
+    }
+    format!("r{hashes}\"{s}\"{hashes}")
+}
+
+/// Replace the value of the constant `name` in a module's source with `value`, which is a literal.
+fn replace_const(module: &str, name: &str, value: &str) -> Result<String, String> {
+    let prefix = format!("const {name}: &str = ");
+    let start = module.find(&prefix).ok_or(format!("Could not find constant {name}."))?
+        + prefix.len();
+    let rest = &module[start..];
+    // A raw string ends with a quote and as many `#`s as it starts with.
+    let end = match rest.strip_prefix('r') {
+        Some(raw) => {
+            let hashes = raw.len() - raw.trim_start_matches('#').len();
+            let close = format!("\"{};", "#".repeat(hashes));
+            raw.get(hashes + 1..).and_then(|body| body.find(&close))
+                .map(|i| 1 + hashes + 1 + i + close.len() - 1)
+        },
+        None => rest.find("\";").map(|i| i + 1)
+    }.ok_or(format!("Could not find the end of constant {name}."))?;
+    Ok(format!("{}{value}{}", &module[..start], &rest[end..]))
+}
+
+/// Write a part's example into the test module of a day's source, enabling its example test.
+fn fill_fixtures(module: &str, part: usize, example: &Example) -> Result<String, String> {
+    let (Some(input), Some(answer)) = (&example.input, &example.answer) else {
+        return Err(format!("Could not find an example and answer for part {part}."))
+    };
+    let module = replace_const(module, &format!("EXAMPLE_{part}"), &raw_literal(input))?;
+    let module = replace_const(&module, &format!("ANSWER_{part}"), &format!("{answer:?}"))?;
+    let ignored = format!(
+        "    #[ignore = \"example not filled in yet\"]\n    fn part_{part}_example()"
+    );
+    Ok(module.replace(&ignored, &format!("    fn part_{part}_example()")))
+}
+
+/// Extract the examples from a saved puzzle page and write them into the test module of `day` in
+/// the source directory `src`. Returns the module's path and the parts filled in.
+pub(crate) fn extract(src: &Path, day: usize, page: &Path)
+    -> Result<(PathBuf, Vec<usize>), String> {
+    let html = fs::read_to_string(page)
+        .map_err(|e| format!("Could not read {}: {e}", page.display()))?;
+    let path = src.join(format!("{}.rs", scaffold::module_name(day)));
+    let mut module = fs::read_to_string(&path)
+        .map_err(|e| format!("Could not read {}: {e}", path.display()))?;
+    let mut filled: Vec<usize> = vec!();
+    for (i, example) in parse_page(&html).iter().enumerate() {
+        module = fill_fixtures(&module, i + 1, example)?;
+        filled.push(i + 1);
+    }
+    if filled.is_empty() {
+        return Err(format!("Found no puzzle descriptions in {}.", page.display()))
+    }
+    fs::write(&path, module).map_err(|e| format!("Could not write {}: {e}", path.display()))?;
+    Ok((path, filled))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAGE: &str = r##"<main>
+<article class="day-desc"><h2>--- Day 1: Test ---</h2>
+<p>Some numbers:</p><pre><code>not this</code></pre>
+<p>For example:</p>
+<pre><code>1abc2
+a&lt;1&gt;"#2
+</code></pre>
+<p>Adding these together produces <code><em>12</em></code>, but <code>3</code> is not.</p>
+<p>Then the total is <code><em>42</em></code>.</p>
+</article>
+<p>Your puzzle answer was <code>55</code>.</p>
+<article class="day-desc"><h2 id="part2">--- Part Two ---</h2>
+<p>Now the total is <em><code>99</code></em>.</p>
+</article>
+</main>"##;
+
+    #[test]
+    fn finds_examples_and_answers() {
+        let parts = parse_page(PAGE);
+        let input = String::from("1abc2\na<1>\"#2\n");
+        assert_eq!(parts, vec!(
+            Example { input: Some(input.clone()), answer: Some(String::from("42")) },
+            Example { input: Some(input), answer: Some(String::from("99")) }
+        ));
+    }
+
+    #[test]
+    fn fills_the_template() {
+        let module = crate::scaffold::render(1);
+        let parts = parse_page(PAGE);
+        let filled = fill_fixtures(&module, 1, &parts[0]).unwrap();
+        assert!(filled.contains("const EXAMPLE_1: &str = r##\"1abc2\na<1>\"#2\n\"##;\n"));
+        assert!(filled.contains("const ANSWER_1: &str = \"42\";"));
+        assert_eq!(filled.matches("#[ignore").count(), 1);
+
+        // Filling in again replaces the earlier fixture, whatever its delimiters.
+        let example = Example { input: Some(String::from("x")), answer: Some(String::from("7")) };
+        let refilled = fill_fixtures(&filled, 1, &example).unwrap();
+        assert!(refilled.contains("const EXAMPLE_1: &str = r#\"x\"#;\n"));
+        assert!(refilled.contains("const ANSWER_1: &str = \"7\";\n    const EXAMPLE_2"));
+    }
+}
@@ -0,0 +1,277 @@
+#![cfg_attr(not(test), allow(dead_code, reason = "day 8 only follows edges; the rest is tested"))]
+
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Display;
+
+/// A directed graph whose nodes are identified by string labels. Labels are interned to dense
+/// `u32` IDs, which index the graph's adjacency lists. Each edge carries a label of type `E`
+/// (eg, the `L` or `R` instruction that selects it), so that successors can be looked up by edge
+/// label.
+#[derive(Clone, Debug)]
+pub(crate) struct Graph<E> {
+    labels: Vec<String>,
+    ids: HashMap<String, u32>,
+    adjacency: Vec<Vec<(E, u32)>>
+}
+
+impl<E> Default for Graph<E> {
+    fn default() -> Self {
+        Graph { labels: vec!(), ids: HashMap::new(), adjacency: vec!() }
+    }
+}
+
+impl<E> Graph<E> {
+
+    pub(crate) fn new() -> Graph<E> {
+        Graph::default()
+    }
+
+    /// Return the ID of the node with the given label, adding the node if it doesn't exist yet.
+    pub(crate) fn add_node(&mut self, label: &str) -> u32 {
+        if let Some(id) = self.ids.get(label) {
+            return *id
+        }
+        let id = self.labels.len() as u32;
+        self.labels.push(String::from(label));
+        self.ids.insert(String::from(label), id);
+        self.adjacency.push(vec!());
+        id
+    }
+
+    /// Add an edge from `from` to `to`, selected by `edge`.
+    pub(crate) fn add_edge(&mut self, from: u32, edge: E, to: u32) {
+        self.adjacency[from as usize].push((edge, to));
+    }
+
+    /// The number of nodes in the graph.
+    pub(crate) fn len(&self) -> usize {
+        self.labels.len()
+    }
+
+    pub(crate) fn id(&self, label: &str) -> Option<u32> {
+        self.ids.get(label).copied()
+    }
+
+    pub(crate) fn label(&self, id: u32) -> &str {
+        &self.labels[id as usize]
+    }
+
+    /// Iterate over the IDs of every node, in the order they were added.
+    pub(crate) fn ids(&self) -> impl Iterator<Item = u32> {
+        0..self.labels.len() as u32
+    }
+
+    /// The outgoing edges of a node, as (edge label, target) pairs in the order they were added.
+    pub(crate) fn edges(&self, id: u32) -> &[(E, u32)] {
+        &self.adjacency[id as usize]
+    }
+
+    /// Iterate over the targets of a node's outgoing edges.
+    pub(crate) fn successors(&self, id: u32) -> impl Iterator<Item = u32> + '_ {
+        self.adjacency[id as usize].iter().map(|(_, to)| *to)
+    }
+
+    /// Return the IDs of every node reachable from `start` (including `start` itself), in
+    /// breadth-first order.
+    pub(crate) fn reachable(&self, start: u32) -> Vec<u32> {
+        let mut seen = vec!(false; self.len());
+        let mut order: Vec<u32> = vec!();
+        let mut queue: VecDeque<u32> = VecDeque::from([start]);
+        seen[start as usize] = true;
+        while let Some(id) = queue.pop_front() {
+            order.push(id);
+            for next in self.successors(id) {
+                if !seen[next as usize] {
+                    seen[next as usize] = true;
+                    queue.push_back(next);
+                }
+            }
+        }
+        order
+    }
+
+    /// Return the strongly connected components of the graph, using an iterative version of
+    /// Tarjan's algorithm. Components are returned in reverse topological order, ie, no component
+    /// has an edge to a component that comes after it.
+    pub(crate) fn strongly_connected_components(&self) -> Vec<Vec<u32>> {
+        let n = self.len();
+        let mut index: Vec<Option<u32>> = vec!(None; n);
+        let mut low_link: Vec<u32> = vec!(0; n);
+        let mut on_stack = vec!(false; n);
+        let mut stack: Vec<u32> = vec!();
+        let mut components: Vec<Vec<u32>> = vec!();
+        let mut next_index = 0;
+
+        for root in self.ids() {
+            if index[root as usize].is_some() {
+                continue
+            }
+            // Each frame is a node and the position of the next of its edges to visit.
+            let mut call_stack: Vec<(u32, usize)> = vec!((root, 0));
+            index[root as usize] = Some(next_index);
+            low_link[root as usize] = next_index;
+            next_index += 1;
+            stack.push(root);
+            on_stack[root as usize] = true;
+
+            while let Some((id, edge_i)) = call_stack.last_mut() {
+                let id = *id;
+                if let Some((_, next)) = self.edges(id).get(*edge_i) {
+                    *edge_i += 1;
+                    let next = *next as usize;
+                    match index[next] {
+                        None => {
+                            index[next] = Some(next_index);
+                            low_link[next] = next_index;
+                            next_index += 1;
+                            stack.push(next as u32);
+                            on_stack[next] = true;
+                            call_stack.push((next as u32, 0));
+                        },
+                        Some(i) if on_stack[next] => {
+                            low_link[id as usize] = low_link[id as usize].min(i);
+                        },
+                        Some(_) => {}
+                    }
+                    continue
+                }
+                // Finished with this node's edges.
+                call_stack.pop();
+                if let Some((parent, _)) = call_stack.last() {
+                    let parent = *parent as usize;
+                    low_link[parent] = low_link[parent].min(low_link[id as usize]);
+                }
+                if Some(low_link[id as usize]) == index[id as usize] {
+                    let mut component: Vec<u32> = vec!();
+                    while let Some(member) = stack.pop() {
+                        on_stack[member as usize] = false;
+                        component.push(member);
+                        if member == id {
+                            break
+                        }
+                    }
+                    components.push(component);
+                }
+            }
+        }
+        components
+    }
+
+    /// Return the IDs of every node ordered so that each edge goes from an earlier node to a later
+    /// one, or an error if the graph has a cycle.
+    pub(crate) fn topological_sort(&self) -> Result<Vec<u32>, String> {
+        let mut in_degree = vec!(0; self.len());
+        for id in self.ids() {
+            for next in self.successors(id) {
+                in_degree[next as usize] += 1;
+            }
+        }
+        let mut queue: VecDeque<u32> = self.ids()
+            .filter(|id| in_degree[*id as usize] == 0)
+            .collect();
+        let mut order: Vec<u32> = vec!();
+        while let Some(id) = queue.pop_front() {
+            order.push(id);
+            for next in self.successors(id) {
+                in_degree[next as usize] -= 1;
+                if in_degree[next as usize] == 0 {
+                    queue.push_back(next);
+                }
+            }
+        }
+        if order.len() < self.len() {
+            return Err(String::from("Graph contains a cycle so cannot be sorted topologically."))
+        }
+        Ok(order)
+    }
+}
+
+impl<E> Graph<E> where E: PartialEq {
+
+    /// Return the target of the edge leaving `id` with the given label, if there is one.
+    pub(crate) fn successor(&self, id: u32, edge: &E) -> Option<u32> {
+        self.adjacency[id as usize].iter().find(|(e, _)| e == edge).map(|(_, to)| *to)
+    }
+}
+
+impl<E> Graph<E> where E: Display {
+
+    /// Render the graph in Graphviz DOT format.
+    pub(crate) fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph {\n");
+        for id in self.ids() {
+            dot.push_str(&format!("    {id} [label={:?}];\n", self.label(id)));
+        }
+        for id in self.ids() {
+            for (edge, to) in self.edges(id) {
+                dot.push_str(&format!("    {id} -> {to} [label={:?}];\n", edge.to_string()));
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Build a graph from `from -> to` edges labelled by their position in the list.
+    fn graph(edges: &[(&str, &str)]) -> Graph<usize> {
+        let mut graph = Graph::new();
+        for (i, (from, to)) in edges.iter().enumerate() {
+            let from = graph.add_node(from);
+            let to = graph.add_node(to);
+            graph.add_edge(from, i, to);
+        }
+        graph
+    }
+
+    fn labels(graph: &Graph<usize>, ids: &[u32]) -> Vec<String> {
+        ids.iter().map(|id| String::from(graph.label(*id))).collect()
+    }
+
+    #[test]
+    fn finds_strongly_connected_components() {
+        // a <-> b -> c -> d -> c, and e on its own.
+        let mut g = graph(&[("a", "b"), ("b", "a"), ("b", "c"), ("c", "d"), ("d", "c")]);
+        g.add_node("e");
+        let mut components: Vec<Vec<String>> = g.strongly_connected_components().iter()
+            .map(|c| {
+                let mut c = labels(&g, c);
+                c.sort();
+                c
+            })
+            .collect();
+        // {c, d} has no edges out, so comes before {a, b}.
+        assert_eq!(components[..2], [vec!("c", "d"), vec!("a", "b")]);
+        components.sort();
+        assert_eq!(components, [vec!("a", "b"), vec!("c", "d"), vec!("e")]);
+        assert_eq!(labels(&g, &g.reachable(g.id("b").unwrap())), ["b", "a", "c", "d"]);
+    }
+
+    #[test]
+    fn sorts_topologically() {
+        let g = graph(&[("shirt", "tie"), ("tie", "jacket"), ("trousers", "shoes"),
+                        ("trousers", "belt"), ("belt", "jacket"), ("shirt", "belt")]);
+        let order = g.topological_sort().unwrap();
+        assert_eq!(order.len(), g.len());
+        let position = |label| order.iter().position(|id| *id == g.id(label).unwrap()).unwrap();
+        for id in g.ids() {
+            for next in g.successors(id) {
+                assert!(position(g.label(id)) < position(g.label(next)));
+            }
+        }
+
+        let cyclic = graph(&[("a", "b"), ("b", "c"), ("c", "a"), ("x", "a")]);
+        assert!(cyclic.topological_sort().is_err());
+    }
+
+    #[test]
+    fn renders_dot() {
+        let g = graph(&[("a", "b\"")]);
+        assert_eq!(g.to_dot(), "digraph {\n    0 [label=\"a\"];\n    1 [label=\"b\\\"\"];\n    \
+                                0 -> 1 [label=\"0\"];\n}\n");
+    }
+}
@@ -0,0 +1,170 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::options::Options;
+use crate::remote::Remote;
+
+/// The puzzle year used if no `year` option is given.
+const DEFAULT_YEAR: u32 = 2023;
+
+/// The cache directory used if no `cache` option is given.
+const DEFAULT_CACHE_DIR: &str = "inputs";
+
+/// Return the puzzle year given by the `year` option.
+pub(crate) fn year(opts: &Options) -> u32 {
+    opts.get("year").map_or(DEFAULT_YEAR, |y| y.parse::<u32>().expect("Could not parse year."))
+}
+
+/// A directory of puzzle inputs, each stored at `<dir>/<year>/day_<dd>.txt`.
+#[derive(Clone, Debug)]
+pub(crate) struct InputCache {
+    dir: PathBuf
+}
+
+impl InputCache {
+
+    pub(crate) fn new(dir: &Path) -> InputCache {
+        InputCache { dir: dir.to_path_buf() }
+    }
+
+    /// The cache in the directory given by the `cache` option (`inputs` by default).
+    pub(crate) fn from_options(opts: &Options) -> InputCache {
+        InputCache::new(Path::new(opts.get("cache").unwrap_or(DEFAULT_CACHE_DIR)))
+    }
+
+    /// The directory holding a year's inputs.
+    pub(crate) fn year_dir(&self, year: u32) -> PathBuf {
+        self.dir.join(year.to_string())
+    }
+
+    pub(crate) fn path(&self, year: u32, day: usize) -> PathBuf {
+        self.year_dir(year).join(format!("day_{day:02}.txt"))
+    }
+
+    pub(crate) fn contains(&self, year: u32, day: usize) -> bool {
+        self.path(year, day).is_file()
+    }
+
+    /// Write an input to the cache, replacing any input already stored for that day, and return
+    /// the path it was written to.
+    pub(crate) fn store(&self, year: u32, day: usize, input: &str) -> Result<PathBuf, String> {
+        let path = self.path(year, day);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Could not create {}: {e}", parent.display()))?;
+        }
+        fs::write(&path, input).map_err(|e| format!("Could not write {}: {e}", path.display()))?;
+        Ok(path)
+    }
+}
+
+/// Split the arguments following a day and part into the input path, if the first is one, and the
+/// options. The first argument is taken as a path if it names a file, or looks like a path by
+/// containing a separator or having an extension, so that a mistyped path isn't taken for a flag.
+pub(crate) fn split_path_arg(args: &[String]) -> (Option<&str>, &[String]) {
+    let is_path = |a: &str| {
+        let path = Path::new(a);
+        path.is_file() || a.contains(std::path::is_separator) || path.extension().is_some()
+    };
+    match args.split_first() {
+        Some((first, rest)) if !first.contains('=') && is_path(first) => {
+            (Some(first.as_str()), rest)
+        },
+        _ => (None, args)
+    }
+}
+
+/// Work out where to read a day's input from: the path `arg` if one was given, or otherwise the
+/// day's input in the cache. Returns an error if `arg` isn't a file or the input isn't cached.
+pub(crate) fn resolve(day: usize, arg: Option<&str>, opts: &Options) -> Result<PathBuf, String> {
+    if let Some(arg) = arg {
+        let path = Path::new(arg);
+        if !path.is_file() {
+            return Err(format!("Input file {} does not exist.", path.display()))
+        }
+        return Ok(path.to_path_buf())
+    }
+    let cache = InputCache::from_options(opts);
+    let year = year(opts);
+    if !cache.contains(year, day) {
+        return Err(format!(
+            "No input found for day {day} of {year} at {}; run `fetch {day}` to download it.",
+            cache.path(year, day).display()
+        ))
+    }
+    Ok(cache.path(year, day))
+}
+
+/// Download a day's input into the cache, unless it is already there and the `force` option isn't
+/// set. Returns the path of the cached input.
+pub(crate) fn fetch(day: usize, opts: &Options) -> Result<PathBuf, String> {
+    let cache = InputCache::from_options(opts);
+    let year = year(opts);
+    if cache.contains(year, day) && !opts.flag("force") {
+        return Ok(cache.path(year, day))
+    }
+    let input = Remote::from_options(opts)?.fetch_input(year, day)?;
+    cache.store(year, day, &input)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::remote::stub;
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| String::from(*a)).collect()
+    }
+
+    #[test]
+    fn tells_paths_from_options() {
+        let file = std::env::temp_dir().join(format!("aoc23-input-{}", std::process::id()));
+        fs::write(&file, "1\n").unwrap();
+        let file_arg = file.to_str().unwrap();
+        let bare = file.file_name().unwrap().to_str().unwrap();
+
+        let with_flag = args(&[file_arg, "big"]);
+        assert_eq!(split_path_arg(&with_flag), (Some(file_arg), &with_flag[1..]));
+        assert_eq!(split_path_arg(&args(&["ties"])), (None, &args(&["ties"])[..]));
+        assert_eq!(split_path_arg(&args(&["k=2", "x.txt"])).0, None);
+        assert_eq!(split_path_arg(&args(&["typo.txt"])).0, Some("typo.txt"));
+        assert_eq!(split_path_arg(&args(&["dir/input"])).0, Some("dir/input"));
+        assert_eq!(split_path_arg(&args(&[])), (None, &args(&[])[..]));
+        // A file without an extension in the working directory is only a path if it exists.
+        assert_eq!(split_path_arg(&args(&[bare])).0, None);
+        fs::remove_file(&file).unwrap();
+    }
+
+    #[test]
+    fn fetches_into_the_cache() {
+        let dir = std::env::temp_dir().join(format!("aoc23-inputs-{}", std::process::id()));
+        let session = dir.join("session");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(&session, "secret\n").unwrap();
+        let options = |url: &str, force: bool| Options::from_args(args(&[
+            &format!("cache={}", dir.display()),
+            &format!("session_file={}", session.display()),
+            &format!("base_url={url}"),
+            &format!("force={force}")
+        ]).into_iter());
+
+        let (url, server) = stub::serve_once("1 2 3\n");
+        let path = fetch(7, &options(&url, false)).unwrap();
+        let (head, _) = server.join().unwrap();
+        assert!(head.starts_with("GET /2023/day/7/input "));
+        assert_eq!(path, dir.join("2023").join("day_07.txt"));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "1 2 3\n");
+        let cache = InputCache::new(&dir);
+        assert!(cache.contains(2023, 7) && !cache.contains(2023, 8));
+
+        // A cached input is used without asking the server, which isn't listening any more.
+        assert_eq!(fetch(7, &options(&url, false)), Ok(path.clone()));
+        assert_eq!(resolve(7, None, &options(&url, false)), Ok(path.clone()));
+
+        // Unless the `force` option is set.
+        let (url, server) = stub::serve_once("4 5 6\n");
+        assert_eq!(fetch(7, &options(&url, true)), Ok(path.clone()));
+        server.join().unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "4 5 6\n");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -3,63 +3,191 @@ mod day_02;
 mod day_03;
 mod day_04;
 mod day_05;
+mod answer;
 mod common;
+mod cycles;
 mod day_06;
 mod day_07;
 mod day_08;
 mod day_09;
 mod day_10;
 mod day_11;
+mod examples;
+mod graph;
+mod inputs;
+mod matcher;
+mod options;
+mod piecewise;
+mod remote;
+mod scaffold;
+mod sequence;
+mod submit;
+mod watch;
 
 use std::{fs, path};
 use std::env;
-use std::time::Instant;
+use std::time::{Duration, Instant};
+use answer::Answer;
+use options::Options;
+use remote::Remote;
+use submit::History;
+
+type Solution = fn(&str, &Options) -> Answer;
+
+const SOLUTIONS: [[Option<Solution>; 2]; 25] = [
+    [Some(day_01::part_1), Some(day_01::part_2)],
+    [Some(day_02::part_1), Some(day_02::part_2)],
+    [Some(day_03::part_1), Some(day_03::part_2)],
+    [Some(day_04::part_1), Some(day_04::part_2)],
+    [Some(day_05::part_1), Some(day_05::part_2)],
+    [Some(day_06::part_1), Some(day_06::part_2)],
+    [Some(day_07::part_1), Some(day_07::part_2)],
+    [Some(day_08::part_1), Some(day_08::part_2)],
+    [Some(day_09::part_1), Some(day_09::part_2)],
+    [Some(day_10::part_1), Some(day_10::part_2)],
+    [Some(day_11::part_1), Some(day_11::part_2)],
+    [None, None],
+    [None, None],
+    [None, None],
+    [None, None],
+    [None, None],
+    [None, None],
+    [None, None],
+    [None, None],
+    [None, None],
+    [None, None],
+    [None, None],
+    [None, None],
+    [None, None],
+    [None, None]
+];
 
 fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    match args.first().map(|a| a.as_str()) {
+        Some("fetch") => fetch(&args[1..]),
+        Some("submit") => submit(&args[1..]),
+        Some("new-day") => new_day(&args[1..]),
+        Some("examples") => examples(&args[1..]),
+        Some("watch") => watch(&args[1..]),
+        _ => run(&args)
+    }
+}
+
+/// Download a day's input into the input cache: `fetch <day> [options]`.
+fn fetch(args: &[String]) {
+    let day = args.first().expect("Must specify a day.")
+        .parse::<usize>().expect("Day must be integer.");
+    let opts = Options::from_args(args[1..].iter().cloned());
+    match inputs::fetch(day, &opts) {
+        Ok(path) => println!("Input for day {} is at {}", day, path.display()),
+        Err(e) => panic!("{}", e)
+    }
+}
+
+/// Create a new day's module from the template and register it: `new-day <day> [options]`. The
+/// module is written to the directory given by the `src` option (`src` by default).
+fn new_day(args: &[String]) {
+    let day = args.first().expect("Must specify a day.")
+        .parse::<usize>().expect("Day must be integer.");
+    let opts = Options::from_args(args[1..].iter().cloned());
+    let src = path::Path::new(opts.get("src").unwrap_or("src"));
+    match scaffold::new_day(src, day) {
+        Ok(path) => println!("Created {} and registered day {}.", path.display(), day),
+        Err(e) => panic!("{}", e)
+    }
+}
 
-    let funcs: [[Option<fn(&str) -> String>; 2]; 25] = [
-        [Some(day_01::part_1), Some(day_01::part_2)],
-        [Some(day_02::part_1), Some(day_02::part_2)],
-        [Some(day_03::part_1), Some(day_03::part_2)],
-        [Some(day_04::part_1), Some(day_04::part_2)],
-        [Some(day_05::part_1), Some(day_05::part_2)],
-        [Some(day_06::part_1), Some(day_06::part_2)],
-        [Some(day_07::part_1), Some(day_07::part_2)],
-        [Some(day_08::part_1), Some(day_08::part_2)],
-        [Some(day_09::part_1), Some(day_09::part_2)],
-        [Some(day_10::part_1), Some(day_10::part_2)],
-        [Some(day_11::part_1), Some(day_11::part_2)],
-        [None, None],
-        [None, None],
-        [None, None],
-        [None, None],
-        [None, None],
-        [None, None],
-        [None, None],
-        [None, None],
-        [None, None],
-        [None, None],
-        [None, None],
-        [None, None],
-        [None, None],
-        [None, None]
-    ];
+/// Fill in a day's example tests from a saved copy of its puzzle page:
+/// `examples <day> <page path> [options]`. The day's module is read from the directory given by
+/// the `src` option (`src` by default).
+fn examples(args: &[String]) {
+    let day = args.first().expect("Must specify a day.")
+        .parse::<usize>().expect("Day must be integer.");
+    let page = path::Path::new(args.get(1).expect("Must specify a path to the puzzle page."));
+    let opts = Options::from_args(args[2..].iter().cloned());
+    let src = path::Path::new(opts.get("src").unwrap_or("src"));
+    match examples::extract(src, day, page) {
+        Ok((path, parts)) => println!("Filled in examples for parts {:?} in {}.", parts,
+                                      path.display()),
+        Err(e) => panic!("{}", e)
+    }
+}
+
+/// Rebuild and rerun a day whenever its source or input changes:
+/// `watch <day> [part] [input path] [options]`. Both parts are run unless one is given. The
+/// `interval` option sets how often to check for changes, in milliseconds (500 by default).
+fn watch(args: &[String]) {
+    let day = args.first().expect("Must specify a day.")
+        .parse::<usize>().expect("Day must be integer.");
+    let part = args.get(1).and_then(|a| a.parse::<usize>().ok());
+    let rest = &args[if part.is_some() { 2 } else { 1 }..];
+    let (path_arg, opt_args) = inputs::split_path_arg(rest);
+    let opts = Options::from_args(opt_args.iter().cloned());
+    let interval = opts.get("interval")
+        .map_or(500, |i| i.parse::<u64>().expect("Could not parse interval."));
+
+    let src = path::Path::new(opts.get("src").unwrap_or("src"));
+    let mut files = vec!(src.join(format!("{}.rs", scaffold::module_name(day))));
+    // Watch the cached input even if it hasn't been fetched yet, to notice when it is.
+    files.push(match path_arg {
+        Some(_) => inputs::resolve(day, path_arg, &opts).unwrap_or_else(|e| panic!("{}", e)),
+        None => inputs::InputCache::from_options(&opts).path(inputs::year(&opts), day)
+    });
+    let run_args: Vec<Vec<String>> = part.map_or(vec!(1, 2), |p| vec!(p)).iter()
+        .map(|p| [day.to_string(), p.to_string()].into_iter().chain(rest.iter().cloned()).collect())
+        .collect();
+    watch::watch(day, files, run_args, Duration::from_millis(interval))
+}
+
+/// Submit an answer to the puzzle website: `submit <day> <part> [options]`. The answer is the
+/// `answer` option if given, and otherwise is found by running the solution on the cached input.
+/// Options for the solution must be given in `key=value` form.
+fn submit(args: &[String]) {
+    let day = args.first().expect("Must specify a day.")
+        .parse::<usize>().expect("Day must be integer.");
+    let part = args.get(1).expect("Must specify a part.")
+        .parse::<usize>().expect("Part must be an integer.");
+    let opts = Options::from_args(args.iter().skip(2).cloned());
+    let answer = match opts.get("answer") {
+        Some(answer) => String::from(answer),
+        None => {
+            let f = SOLUTIONS[day-1][part-1]
+                .unwrap_or_else(|| panic!("No function found for day {}, part {}.", day, part));
+            let input_fpath = inputs::resolve(day, None, &opts)
+                .unwrap_or_else(|e| panic!("{}", e));
+            let input = fs::read_to_string(input_fpath).expect("Could not read input file.");
+            f(&input, &opts).to_string()
+        }
+    };
+    let remote = Remote::from_options(&opts).unwrap_or_else(|e| panic!("{}", e));
+    let mut history = History::from_options(&opts).unwrap_or_else(|e| panic!("{}", e));
+    match history.submit(&remote, inputs::year(&opts), day, part, &answer, submit::now()) {
+        Ok(verdict) => println!("Day {}, part {}: submitted {}, verdict: {}", day, part, answer,
+                                verdict),
+        Err(e) => println!("Day {}, part {}: did not submit {}. {}", day, part, answer, e)
+    }
+}
 
-    let day = env::args().nth(1).expect("Must specify a day.")
+/// Run a solution: `<day> <part> [input path] [options]`. If no input path is given, the day's
+/// input is read from the input cache.
+fn run(args: &[String]) {
+    let day = args.first().expect("Must specify a day.")
         .parse::<usize>().expect("Day must be integer.");
-    let part = env::args().nth(2).expect("Must specify a part.")
+    let part = args.get(1).expect("Must specify a part.")
         .parse::<usize>().expect("Part must be an integer.");
-    let input_fpath_str = env::args().nth(3)
-        .expect("Must specify a path to input data.");
-    let input_fpath = path::Path::new(&input_fpath_str);
-    let func = &funcs[day-1][part-1];
+    let (path_arg, opt_args) = inputs::split_path_arg(&args[2..]);
+    let opts = Options::from_args(opt_args.iter().cloned());
+    let input_fpath = inputs::resolve(day, path_arg, &opts)
+        .unwrap_or_else(|e| panic!("{}", e));
+    let func = &SOLUTIONS[day-1][part-1];
 
     match func {
         Some(f) => {
             let start_time = Instant::now();
             let input = fs::read_to_string(input_fpath)
                 .expect("Could not read input file.");
-            let output = f(&input);
+            let output = f(&input, &opts);
             let end_time = Instant::now();
             let duration = end_time - start_time;
             println!("Day {}, part {} answer:", day, part);
@@ -0,0 +1,202 @@
+use std::collections::{HashMap, VecDeque};
+
+/// An Aho–Corasick automaton that finds occurrences of any of a set of patterns in a single pass
+/// over a sequence of characters.
+#[derive(Clone, Debug)]
+struct Automaton {
+    /// Transitions out of each state of the trie of patterns. State 0 is the root.
+    goto: Vec<HashMap<char, usize>>,
+    /// For each state, the state for the longest proper suffix of its string that is also in the
+    /// trie.
+    fail: Vec<usize>,
+    /// For each state, the indices of the patterns that end at it, including via its fail links.
+    output: Vec<Vec<usize>>
+}
+
+impl Automaton {
+
+    fn new<'a, I>(patterns: I) -> Automaton where I: Iterator<Item = &'a [char]> {
+        let mut goto: Vec<HashMap<char, usize>> = vec!(HashMap::new());
+        let mut output: Vec<Vec<usize>> = vec!(vec!());
+        for (i, pattern) in patterns.enumerate() {
+            let mut state = 0;
+            for c in pattern {
+                state = match goto[state].get(c) {
+                    Some(next) => *next,
+                    None => {
+                        goto.push(HashMap::new());
+                        output.push(vec!());
+                        let next = goto.len() - 1;
+                        goto[state].insert(*c, next);
+                        next
+                    }
+                };
+            }
+            output[state].push(i);
+        }
+
+        // Compute fail links breadth-first, so that each state's fail link is known before its
+        // children's are.
+        let mut fail = vec!(0; goto.len());
+        let mut queue: VecDeque<usize> = goto[0].values().copied().collect();
+        while let Some(state) = queue.pop_front() {
+            let children: Vec<(char, usize)> = goto[state].iter()
+                .map(|(c, s)| (*c, *s))
+                .collect();
+            for (c, child) in children {
+                let mut f = fail[state];
+                while f != 0 && !goto[f].contains_key(&c) {
+                    f = fail[f];
+                }
+                fail[child] = goto[f].get(&c).copied().unwrap_or(0);
+                let inherited = output[fail[child]].clone();
+                output[child].extend(inherited);
+                queue.push_back(child);
+            }
+        }
+        Automaton { goto, fail, output }
+    }
+
+    fn next_state(&self, mut state: usize, c: char) -> usize {
+        loop {
+            if let Some(next) = self.goto[state].get(&c) {
+                return *next
+            }
+            if state == 0 {
+                return 0
+            }
+            state = self.fail[state];
+        }
+    }
+
+    /// Return the index of the pattern whose occurrence starts earliest in `chars`, along with the
+    /// position at which it starts. Where several occurrences start at the same position, the
+    /// longest wins. Stops reading as soon as no later occurrence could start any earlier.
+    fn leftmost<I>(&self, chars: I, lengths: &[usize], max_len: usize) -> Option<(usize, usize)>
+        where I: Iterator<Item = char> {
+        let mut state = 0;
+        let mut best: Option<(usize, usize)> = None;
+        for (end, c) in chars.enumerate() {
+            if let Some((_, start)) = best {
+                if end >= start + max_len {
+                    break
+                }
+            }
+            state = self.next_state(state, c);
+            for p in &self.output[state] {
+                let start = end + 1 - lengths[*p];
+                let better = match best {
+                    None => true,
+                    Some((best_p, best_start)) => start < best_start
+                        || (start == best_start && lengths[*p] > lengths[best_p])
+                };
+                if better {
+                    best = Some((*p, start));
+                }
+            }
+        }
+        best
+    }
+}
+
+/// Finds the first and last occurrences in a string of any of a set of patterns, each of which
+/// is associated with a value. Occurrences may overlap, so in `eightwo` the first occurrence is
+/// `eight` and the last is `two`.
+#[derive(Clone, Debug)]
+pub(crate) struct Matcher<V> {
+    values: Vec<V>,
+    lengths: Vec<usize>,
+    max_len: usize,
+    forward: Automaton,
+    /// Matches the reversed patterns against the reversed string, to search from the end.
+    backward: Automaton
+}
+
+impl<V> Matcher<V> {
+
+    /// Create a matcher from (pattern, value) pairs. Empty patterns are ignored.
+    pub(crate) fn new<'a, I>(patterns: I) -> Matcher<V> where I: IntoIterator<Item = (&'a str, V)> {
+        let mut chars: Vec<Vec<char>> = vec!();
+        let mut values: Vec<V> = vec!();
+        for (pattern, value) in patterns {
+            if pattern.is_empty() {
+                continue
+            }
+            chars.push(pattern.chars().collect());
+            values.push(value);
+        }
+        let reversed: Vec<Vec<char>> = chars.iter()
+            .map(|p| p.iter().rev().copied().collect())
+            .collect();
+        Matcher {
+            values,
+            lengths: chars.iter().map(|p| p.len()).collect(),
+            max_len: chars.iter().map(|p| p.len()).max().unwrap_or(0),
+            forward: Automaton::new(chars.iter().map(|p| p.as_slice())),
+            backward: Automaton::new(reversed.iter().map(|p| p.as_slice()))
+        }
+    }
+
+    /// Return the value of the pattern occurrence that starts first in `s`.
+    pub(crate) fn first(&self, s: &str) -> Option<&V> {
+        self.forward.leftmost(s.chars(), &self.lengths, self.max_len)
+            .map(|(p, _)| &self.values[p])
+    }
+
+    /// Return the value of the pattern occurrence that ends last in `s`.
+    pub(crate) fn last(&self, s: &str) -> Option<&V> {
+        self.backward.leftmost(s.chars().rev(), &self.lengths, self.max_len)
+            .map(|(p, _)| &self.values[p])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use super::*;
+
+    fn english() -> Matcher<u32> {
+        Matcher::new([("one", 1), ("two", 2), ("three", 3), ("eight", 8), ("nine", 9), ("1", 1)])
+    }
+
+    #[test]
+    fn finds_overlapping_words() {
+        let m = english();
+        assert_eq!((m.first("eightwo"), m.last("eightwo")), (Some(&8), Some(&2)));
+        assert_eq!((m.first("oneight"), m.last("oneight")), (Some(&1), Some(&8)));
+        assert_eq!((m.first("twone"), m.last("twone")), (Some(&2), Some(&1)));
+        assert_eq!((m.first("xtwonenine1x"), m.last("xtwonenine1x")), (Some(&2), Some(&1)));
+        assert_eq!((m.first("abc"), m.last("abc")), (None, None));
+    }
+
+    #[test]
+    fn prefers_the_longest_pattern_at_a_position() {
+        let m = Matcher::new([("ab", 'x'), ("abc", 'y'), ("c", 'z')]);
+        assert_eq!(m.first("abcd"), Some(&'y'));
+        // `abc` and `c` both end last, and the longer wins.
+        assert_eq!(m.last("abcd"), Some(&'y'));
+        assert_eq!(m.last("abcab"), Some(&'x'));
+        assert_eq!(m.first("xcab"), Some(&'z'));
+    }
+
+    #[test]
+    fn uses_custom_patterns() {
+        let m = Matcher::new([("zero", 0), ("nul", 0), ("", 5), ("sept", 7)]);
+        assert_eq!(m.first("xxseptzero"), Some(&7));
+        assert_eq!(m.last("xxseptzero"), Some(&0));
+        assert_eq!(m.first("null"), Some(&0));
+        assert_eq!(m.first("five"), None);
+    }
+
+    #[test]
+    fn stops_once_no_earlier_match_is_possible() {
+        let m = english();
+        let read = Cell::new(0);
+        let chars = "xeightwo".chars()
+            .chain(std::iter::repeat_n('z', 100))
+            .inspect(|_| read.set(read.get() + 1));
+        assert_eq!(m.forward.leftmost(chars, &m.lengths, m.max_len), Some((3, 1)));
+        // The match starting at 1 rules out any later start, so reading stops after 1 + 5.
+        assert_eq!(read.get(), 7);
+    }
+}
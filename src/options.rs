@@ -0,0 +1,31 @@
+use std::collections::HashMap;
+
+/// Solver-specific settings passed to the runner after the input path, each in the form
+/// `key=value`. A bare `key` is treated as `key=true`.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Options {
+    values: HashMap<String, String>
+}
+
+impl Options {
+
+    pub(crate) fn from_args<I>(args: I) -> Options where I: Iterator<Item = String> {
+        let mut values: HashMap<String, String> = HashMap::new();
+        for arg in args {
+            match arg.split_once('=') {
+                Some((k, v)) => values.insert(String::from(k), String::from(v)),
+                None => values.insert(arg, String::from("true"))
+            };
+        }
+        Options { values }
+    }
+
+    pub(crate) fn get(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(|v| v.as_str())
+    }
+
+    /// Whether the flag `key` was given, either bare or with any value other than `false`.
+    pub(crate) fn flag(&self, key: &str) -> bool {
+        self.get(key).is_some_and(|v| v != "false")
+    }
+}
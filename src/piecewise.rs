@@ -0,0 +1,341 @@
+
+use std::cmp::{max, min};
+
+/// The largest magnitude of any value in a map's segments. Keeping every value within this bound
+/// means that no offset, range end or composition of maps can overflow an `i64`. Values passed to
+/// [`PiecewiseMap::get`] and [`PiecewiseMap::min_image`] must also be within it.
+pub(crate) const MAX_VALUE: i64 = 1 << 61;
+
+/// Maps the `range_len` integers starting at `src_start` onto the same number of integers starting
+/// at `dst_start`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) struct RangeMap {
+    pub(crate) dst_start: i64,
+    pub(crate) src_start: i64,
+    pub(crate) range_len: i64
+}
+
+impl RangeMap {
+
+    pub(crate) fn new(dst_start: i64, src_start: i64, range_len: i64) -> RangeMap {
+        RangeMap { dst_start, src_start, range_len }
+    }
+
+    /// The end of the source range (exclusive).
+    pub(crate) fn src_end(&self) -> i64 {
+        self.src_start + self.range_len
+    }
+
+    /// The end of the destination range (exclusive).
+    pub(crate) fn dst_end(&self) -> i64 {
+        self.dst_start + self.range_len
+    }
+
+    /// How much must be added to a source value to map it to its destination value.
+    pub(crate) fn offset(&self) -> i64 {
+        self.dst_start - self.src_start
+    }
+
+    pub(crate) fn get(&self, k: i64) -> Option<i64> {
+        if k < self.src_start || k >= self.src_end() {
+            return None
+        }
+        Some(k + self.offset())
+    }
+
+    fn inverse(&self) -> RangeMap {
+        RangeMap::new(self.src_start, self.dst_start, self.range_len)
+    }
+}
+
+/// A mapping from integers to integers made up of non-overlapping [`RangeMap`]s. Any value not
+/// covered by a segment maps to itself.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct PiecewiseMap {
+    /// Sorted by source start, non-overlapping, with no empty or identity segments and no two
+    /// adjacent segments sharing an offset.
+    segments: Vec<RangeMap>
+}
+
+impl PiecewiseMap {
+
+    /// Create a map from the given segments, which may be in any order. Returns an error if any
+    /// segment has a negative length, if any of its values are beyond [`MAX_VALUE`] or if the
+    /// source ranges of any two segments overlap.
+    pub(crate) fn new(mut segments: Vec<RangeMap>) -> Result<PiecewiseMap, String> {
+        if let Some(s) = segments.iter().find(|s| s.range_len < 0) {
+            return Err(format!("Segment {s:?} has negative length."))
+        }
+        let in_bounds = |s: &RangeMap| [s.src_start, s.dst_start].iter()
+            .all(|start| (-MAX_VALUE..=MAX_VALUE).contains(start)
+                && s.range_len <= MAX_VALUE - start);
+        if let Some(s) = segments.iter().find(|s| !in_bounds(s)) {
+            return Err(format!("Segment {s:?} has values beyond ±{MAX_VALUE}."))
+        }
+        segments.retain(|s| s.range_len > 0);
+        segments.sort_by_key(|s| s.src_start);
+        if let Some((a, b)) = Self::overlaps(&segments).first() {
+            return Err(format!("Source ranges of segments {a:?} and {b:?} overlap."))
+        }
+        Ok(Self::from_sorted(segments))
+    }
+
+    /// Return every pair of segments whose source ranges overlap.
+    pub(crate) fn overlaps(segments: &[RangeMap]) -> Vec<(RangeMap, RangeMap)> {
+        let mut sorted: Vec<RangeMap> = segments.iter()
+            .filter(|s| s.range_len > 0)
+            .copied()
+            .collect();
+        sorted.sort_by_key(|s| s.src_start);
+        let mut pairs: Vec<(RangeMap, RangeMap)> = vec!();
+        for (i, a) in sorted.iter().enumerate() {
+            for b in sorted[i+1..].iter().take_while(|b| b.src_start < a.src_end()) {
+                pairs.push((*a, *b));
+            }
+        }
+        pairs
+    }
+
+    /// Build a map from segments that are already sorted and known not to overlap, merging
+    /// neighbours where possible and dropping segments that map values to themselves.
+    fn from_sorted(segments: Vec<RangeMap>) -> PiecewiseMap {
+        let mut merged: Vec<RangeMap> = vec!();
+        for s in segments {
+            if s.range_len == 0 || s.offset() == 0 {
+                continue
+            }
+            match merged.last_mut() {
+                Some(last) if last.src_end() == s.src_start && last.offset() == s.offset() => {
+                    last.range_len += s.range_len;
+                },
+                _ => merged.push(s)
+            }
+        }
+        PiecewiseMap { segments: merged }
+    }
+
+    #[cfg(test)]
+    pub(crate) fn segments(&self) -> &[RangeMap] {
+        &self.segments
+    }
+
+    /// Map a single value.
+    pub(crate) fn get(&self, k: i64) -> i64 {
+        let i = self.segments.partition_point(|s| s.src_start <= k);
+        if i == 0 {
+            return k
+        }
+        self.segments[i-1].get(k).unwrap_or(k)
+    }
+
+    /// Return the offset applied at `k`, and how many values starting at `k` share that offset.
+    fn run_from(&self, k: i64) -> (i64, i64) {
+        let i = self.segments.partition_point(|s| s.src_start <= k);
+        if i > 0 && self.segments[i-1].src_end() > k {
+            let s = &self.segments[i-1];
+            return (s.offset(), s.src_end() - k)
+        }
+        match self.segments.get(i) {
+            Some(next) => (0, next.src_start - k),
+            None => (0, i64::MAX)
+        }
+    }
+
+    /// The smallest range containing every segment's source range.
+    fn src_bounds(&self) -> Option<(i64, i64)> {
+        Some((self.segments.first()?.src_start, self.segments.last()?.src_end()))
+    }
+
+    /// Return segments covering every value from `lo` to `hi` (exclusive), filling the gaps
+    /// between this map's segments with identity segments. `lo` and `hi` must not fall inside
+    /// the map's source bounds.
+    fn pieces(&self, lo: i64, hi: i64) -> Vec<RangeMap> {
+        let mut pieces: Vec<RangeMap> = vec!();
+        let mut cursor = lo;
+        for s in &self.segments {
+            if s.src_start > cursor {
+                pieces.push(RangeMap::new(cursor, cursor, s.src_start - cursor));
+            }
+            pieces.push(*s);
+            cursor = s.src_end();
+        }
+        if hi > cursor {
+            pieces.push(RangeMap::new(cursor, cursor, hi - cursor));
+        }
+        pieces
+    }
+
+    /// Return a single map equivalent to applying this map and then `then`.
+    pub(crate) fn compose(&self, then: &PiecewiseMap) -> PiecewiseMap {
+        let (lo, hi) = match (self.src_bounds(), then.src_bounds()) {
+            (None, _) => return then.clone(),
+            (_, None) => return self.clone(),
+            (Some((lo1, hi1)), Some((lo2, hi2))) => (min(lo1, lo2), max(hi1, hi2))
+        };
+        let mut segments: Vec<RangeMap> = vec!();
+        for piece in self.pieces(lo, hi) {
+            // Split the image of each piece wherever `then` changes offset.
+            let mut start = piece.src_start;
+            while start < piece.src_end() {
+                let image = start + piece.offset();
+                let (then_offset, run) = then.run_from(image);
+                let len = min(piece.src_end() - start, run);
+                segments.push(RangeMap::new(image + then_offset, start, len));
+                start += len;
+            }
+        }
+        Self::from_sorted(segments)
+    }
+
+    /// Return the inverse of this map, or an error if two values map to the same value.
+    pub(crate) fn invert(&self) -> Result<PiecewiseMap, String> {
+        let Some((src_lo, src_hi)) = self.src_bounds() else {
+            return Ok(self.clone())
+        };
+        let dst_lo = self.segments.iter().map(|s| s.dst_start).min().unwrap_or(src_lo);
+        let dst_hi = self.segments.iter().map(|s| s.dst_end()).max().unwrap_or(src_hi);
+        let inverted: Vec<RangeMap> = self.pieces(min(src_lo, dst_lo), max(src_hi, dst_hi))
+            .iter()
+            .map(RangeMap::inverse)
+            .collect();
+        PiecewiseMap::new(inverted)
+            .map_err(|_| String::from("Map is not invertible as its destination ranges overlap."))
+    }
+
+    /// Return the smallest value that any of the values from `start` to `end` (exclusive) maps to,
+    /// or None if the range is empty.
+    pub(crate) fn min_image(&self, start: i64, end: i64) -> Option<i64> {
+        if start >= end {
+            return None
+        }
+        let (lo, hi) = match self.src_bounds() {
+            Some((lo, hi)) => (min(lo, start), max(hi, end)),
+            None => return Some(start)
+        };
+        // Within each piece values increase with the input, so the smallest image of a piece's
+        // overlap with the range is the image of the overlap's first value.
+        self.pieces(lo, hi).iter()
+            .filter(|p| p.src_start < end && p.src_end() > start)
+            .map(|p| max(p.src_start, start) + p.offset())
+            .min()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::Lcg;
+    use super::*;
+
+    #[test]
+    fn rejects_out_of_bounds_segments() {
+        assert!(PiecewiseMap::new(vec!(RangeMap::new(0, i64::MIN, 1))).is_err());
+        assert!(PiecewiseMap::new(vec!(RangeMap::new(i64::MAX, 0, 1))).is_err());
+        assert!(PiecewiseMap::new(vec!(RangeMap::new(0, MAX_VALUE - 1, 2))).is_err());
+        assert!(PiecewiseMap::new(vec!(RangeMap::new(-MAX_VALUE, 0, MAX_VALUE))).is_ok());
+    }
+
+    /// The values that brute-force checks map, which extend past every generated segment.
+    const DOMAIN: std::ops::Range<i64> = -5..45;
+
+    /// Generate up to four segments within 0..40, which may overlap.
+    fn random_segments(rng: &mut Lcg) -> Vec<RangeMap> {
+        (0..rng.below(5))
+            .map(|_| RangeMap::new(rng.below(30), rng.below(30), rng.below(10)))
+            .collect()
+    }
+
+    /// Map `k` by checking every segment in turn.
+    fn brute_get(segments: &[RangeMap], k: i64) -> i64 {
+        segments.iter().find_map(|s| s.get(k)).unwrap_or(k)
+    }
+
+    /// Generate a map from segments that don't overlap, along with the segments.
+    fn random_map(rng: &mut Lcg) -> (PiecewiseMap, Vec<RangeMap>) {
+        loop {
+            let segments = random_segments(rng);
+            if let Ok(map) = PiecewiseMap::new(segments.clone()) {
+                return (map, segments)
+            }
+        }
+    }
+
+    #[test]
+    fn finds_overlaps() {
+        let mut rng = Lcg(1);
+        for _ in 0..500 {
+            let segments = random_segments(&mut rng);
+            let overlapping = segments.iter().enumerate().any(|(i, a)| segments[i+1..].iter()
+                .any(|b| DOMAIN.clone().any(|k| a.get(k).is_some() && b.get(k).is_some())));
+            assert_eq!(!PiecewiseMap::overlaps(&segments).is_empty(), overlapping, "{segments:?}");
+            assert_eq!(PiecewiseMap::new(segments.clone()).is_err(), overlapping);
+        }
+    }
+
+    #[test]
+    fn maps_like_its_segments() {
+        let mut rng = Lcg(2);
+        for _ in 0..200 {
+            let (map, segments) = random_map(&mut rng);
+            for k in DOMAIN {
+                assert_eq!(map.get(k), brute_get(&segments, k), "{segments:?} at {k}");
+            }
+            for w in map.segments().windows(2) {
+                assert!(w[0].src_end() < w[1].src_start || w[0].offset() != w[1].offset());
+            }
+        }
+    }
+
+    #[test]
+    fn composes_maps() {
+        let mut rng = Lcg(3);
+        for _ in 0..200 {
+            let (first, first_segments) = random_map(&mut rng);
+            let (then, then_segments) = random_map(&mut rng);
+            let composed = first.compose(&then);
+            for k in DOMAIN {
+                let expected = brute_get(&then_segments, brute_get(&first_segments, k));
+                assert_eq!(composed.get(k), expected, "{first:?} then {then:?} at {k}");
+            }
+        }
+    }
+
+    #[test]
+    fn inverts_maps() {
+        let mut rng = Lcg(4);
+        let (mut invertible, mut not_invertible) = (0, 0);
+        for _ in 0..300 {
+            let (map, segments) = random_map(&mut rng);
+            // Every value beyond the segments maps to itself, so two values share an image
+            // exactly when they do within the segments' bounds.
+            let images: Vec<i64> = DOMAIN.map(|k| brute_get(&segments, k)).collect();
+            let injective = images.iter().enumerate().all(|(i, a)| !images[i+1..].contains(a));
+            match map.invert() {
+                Ok(inverse) => {
+                    assert!(injective, "{map:?} was inverted");
+                    invertible += 1;
+                    for k in DOMAIN {
+                        assert_eq!(inverse.get(map.get(k)), k, "{map:?} at {k}");
+                        assert_eq!(map.get(inverse.get(k)), k, "{map:?} at {k}");
+                    }
+                },
+                Err(_) => {
+                    assert!(!injective, "{map:?} was not inverted");
+                    not_invertible += 1;
+                }
+            }
+        }
+        assert!(invertible > 0 && not_invertible > 0);
+    }
+
+    #[test]
+    fn finds_minimum_images() {
+        let mut rng = Lcg(5);
+        for _ in 0..200 {
+            let (map, segments) = random_map(&mut rng);
+            let start = rng.below(40) - 5;
+            let end = start + rng.below(20);
+            let expected = (start..end).map(|k| brute_get(&segments, k)).min();
+            assert_eq!(map.min_image(start, end), expected, "{map:?} on {start}..{end}");
+        }
+    }
+}
@@ -0,0 +1,133 @@
+use std::{env, fs};
+use crate::options::Options;
+
+/// The puzzle website used if no `base_url` option is given.
+const DEFAULT_BASE_URL: &str = "https://adventofcode.com";
+
+/// The file the session token is read from if no `session_file` option is given.
+const DEFAULT_SESSION_FILE: &str = ".session";
+
+/// The environment variable that, if set, holds the session token. It takes precedence over the
+/// session file.
+const SESSION_ENV_VAR: &str = "AOC_SESSION";
+
+/// Identifies this tool to the website, as its automation guidelines ask.
+const USER_AGENT: &str = "aoc23 puzzle runner";
+
+/// A logged-in connection to the puzzle website, or to a stand-in for it such as a local stub
+/// server.
+#[derive(Clone, Debug)]
+pub(crate) struct Remote {
+    base_url: String,
+    session: String
+}
+
+impl Remote {
+
+    pub(crate) fn new(base_url: &str, session: &str) -> Remote {
+        Remote {
+            base_url: String::from(base_url.trim_end_matches('/')),
+            session: String::from(session)
+        }
+    }
+
+    /// Create a remote for the `base_url` option, reading the session token from the
+    /// `AOC_SESSION` environment variable or, failing that, from the file named by the
+    /// `session_file` option (`.session` by default).
+    pub(crate) fn from_options(opts: &Options) -> Result<Remote, String> {
+        let session = match env::var(SESSION_ENV_VAR) {
+            Ok(token) => token,
+            Err(_) => {
+                let path = opts.get("session_file").unwrap_or(DEFAULT_SESSION_FILE);
+                fs::read_to_string(path).map_err(|e| format!(
+                    "Could not read session token from {path} or ${SESSION_ENV_VAR}: {e}"
+                ))?
+            }
+        };
+        let session = session.trim();
+        if session.is_empty() {
+            return Err(String::from("Session token is empty."))
+        }
+        Ok(Remote::new(opts.get("base_url").unwrap_or(DEFAULT_BASE_URL), session))
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{path}", self.base_url)
+    }
+
+    /// Send a GET request for `path`, returning the body of the response.
+    pub(crate) fn get(&self, path: &str) -> Result<String, String> {
+        let url = self.url(path);
+        ureq::get(&url)
+            .header("Cookie", &format!("session={}", self.session))
+            .header("User-Agent", USER_AGENT)
+            .call()
+            .map_err(|e| format!("Request to {url} failed: {e}"))?
+            .body_mut()
+            .read_to_string()
+            .map_err(|e| format!("Could not read response from {url}: {e}"))
+    }
+
+    /// Send a POST request for `path` with the given form fields, returning the body of the
+    /// response.
+    pub(crate) fn post_form(&self, path: &str, form: &[(&str, &str)]) -> Result<String, String> {
+        let url = self.url(path);
+        ureq::post(&url)
+            .header("Cookie", &format!("session={}", self.session))
+            .header("User-Agent", USER_AGENT)
+            .send_form(form.iter().copied())
+            .map_err(|e| format!("Request to {url} failed: {e}"))?
+            .body_mut()
+            .read_to_string()
+            .map_err(|e| format!("Could not read response from {url}: {e}"))
+    }
+
+    /// Fetch the puzzle input for a day.
+    pub(crate) fn fetch_input(&self, year: u32, day: usize) -> Result<String, String> {
+        self.get(&format!("/{year}/day/{day}/input"))
+    }
+}
+
+/// A stand-in for the puzzle website, for testing.
+#[cfg(test)]
+pub(crate) mod stub {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    /// Serve a single HTTP request with the given response body, returning the base URL of the
+    /// server and a handle that yields the request's head and body.
+    pub(crate) fn serve_once(response: &'static str)
+        -> (String, thread::JoinHandle<(String, String)>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let handle = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut head = String::new();
+            let mut content_length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.trim().is_empty() {
+                    break
+                }
+                if let Some((k, v)) = line.split_once(':') {
+                    if k.eq_ignore_ascii_case("content-length") {
+                        content_length = v.trim().parse().unwrap();
+                    }
+                }
+                head.push_str(&line);
+            }
+            let mut body = vec!(0; content_length);
+            reader.read_exact(&mut body).unwrap();
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{response}",
+                response.len()
+            ).unwrap();
+            (head, String::from_utf8(body).unwrap())
+        });
+        (url, handle)
+    }
+}
@@ -0,0 +1,123 @@
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// The template for a new day's module. `{{DAY}}` is replaced by the day number.
+const TEMPLATE: &str = include_str!("../templates/day.rs.tmpl");
+
+pub(crate) fn module_name(day: usize) -> String {
+    format!("day_{day:02}")
+}
+
+/// Return the source of a new module for `day`.
+pub(crate) fn render(day: usize) -> String {
+    TEMPLATE.replace("{{DAY}}", &day.to_string())
+}
+
+/// Return the runner's source with the module for `day` declared and its parts added to the
+/// solution table, or an error if either has already been done.
+fn register(main: &str, day: usize) -> Result<String, String> {
+    let name = module_name(day);
+    let declaration = format!("mod {name};");
+    let mut lines: Vec<String> = main.lines().map(String::from).collect();
+    if lines.iter().any(|l| l.trim() == declaration) {
+        return Err(format!("Module {name} is already declared."))
+    }
+
+    // Fill in the day's row of the table, which must still be empty.
+    let table_start = lines.iter()
+        .position(|l| l.starts_with("const SOLUTIONS"))
+        .ok_or("Could not find the solution table.")?;
+    let row = lines.get_mut(table_start + day)
+        .ok_or(format!("The solution table has no row for day {day}."))?;
+    let (trimmed, comma) = match row.trim().strip_suffix(',') {
+        Some(r) => (r, ","),
+        None => (row.trim(), "")
+    };
+    if trimmed != "[None, None]" {
+        return Err(format!("Day {day} already has solutions registered: `{trimmed}`."))
+    }
+    *row = format!("    [Some({name}::part_1), Some({name}::part_2)]{comma}");
+
+    // Declare the module before the first later day, or after the last earlier one.
+    let day_mods: Vec<(usize, &str)> = lines.iter()
+        .enumerate()
+        .filter_map(|(i, l)| Some((i, l.strip_prefix("mod day_")?.strip_suffix(';')?)))
+        .collect();
+    let at = match day_mods.iter().find(|(_, n)| *n > name.trim_start_matches("day_")) {
+        Some((i, _)) => *i,
+        None => day_mods.last().map_or(0, |(i, _)| i + 1)
+    };
+    lines.insert(at, declaration);
+    Ok(lines.join("\n") + "\n")
+}
+
+/// Create the module for `day` in the source directory `src` from the template and register it
+/// with the runner. Refuses to touch a day that already has a module or registered solutions.
+pub(crate) fn new_day(src: &Path, day: usize) -> Result<PathBuf, String> {
+    if !(1..=25).contains(&day) {
+        return Err(format!("Day {day} is not between 1 and 25."))
+    }
+    let path = src.join(format!("{}.rs", module_name(day)));
+    if path.exists() {
+        return Err(format!("{} already exists.", path.display()))
+    }
+    let main_path = src.join("main.rs");
+    let main = fs::read_to_string(&main_path)
+        .map_err(|e| format!("Could not read {}: {e}", main_path.display()))?;
+    let main = register(&main, day)?;
+    // `create_new` guards against the module appearing since we checked.
+    fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&path)
+        .and_then(|mut f| f.write_all(render(day).as_bytes()))
+        .map_err(|e| format!("Could not create {}: {e}", path.display()))?;
+    fs::write(&main_path, main)
+        .map_err(|e| format!("Could not write {}: {e}", main_path.display()))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAIN: &str = "\
+mod day_01;
+mod common;
+mod day_03;
+
+const SOLUTIONS: [[Option<Solution>; 2]; 3] = [
+    [Some(day_01::part_1), Some(day_01::part_2)],
+    [None, None],
+    [None, None]
+];
+";
+
+    #[test]
+    fn registers_new_days_in_order() {
+        let main = register(MAIN, 2).unwrap();
+        assert!(main.contains("mod day_01;\nmod common;\nmod day_02;\nmod day_03;\n"));
+        let row = "    [Some(day_02::part_1), Some(day_02::part_2)],\n    [None, None]\n";
+        assert!(main.contains(row));
+
+        let main = register(MAIN.replace("mod day_03;\n", "").as_str(), 3).unwrap();
+        assert!(main.contains("mod day_01;\nmod day_03;\nmod common;\n"));
+        assert!(main.contains("    [Some(day_03::part_1), Some(day_03::part_2)]\n];"));
+    }
+
+    #[test]
+    fn refuses_registered_days() {
+        assert!(register(MAIN, 1).is_err());
+        assert!(register(MAIN, 3).is_err());
+        assert!(register(&register(MAIN, 2).unwrap(), 2).is_err());
+    }
+
+    #[test]
+    fn template_declares_both_parts() {
+        let module = render(12);
+        assert!(module.contains("todo!(\"Day 12, part 1\")"));
+        assert!(module.contains("pub(crate) fn part_2("));
+        assert!(!module.contains("{{"));
+    }
+}
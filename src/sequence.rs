@@ -0,0 +1,202 @@
+#![cfg_attr(not(test), allow(dead_code, reason = "day 9 only extrapolates; the rest is tested"))]
+
+use num::{CheckedAdd, CheckedMul, CheckedSub, Rational64, Zero};
+
+/// Return the differences between each consecutive pair of values in the sequence, or None if any
+/// difference overflows.
+pub(crate) fn differences<T>(seq: &[T]) -> Option<Vec<T>> where T: CheckedSub {
+    seq.windows(2).map(|w| w[1].checked_sub(&w[0])).collect()
+}
+
+/// Return the finite difference table of a sequence: the sequence itself, then its differences,
+/// then the differences of those, and so on. Stops at the first row that is all zeros, or at a row
+/// with a single value if no row is all zeros. Returns None if any difference overflows.
+pub(crate) fn difference_table<T>(seq: &[T]) -> Option<Vec<Vec<T>>>
+    where T: CheckedSub + Zero + Clone {
+    let mut table: Vec<Vec<T>> = vec!(seq.to_vec());
+    loop {
+        let last = table.last().unwrap();
+        if last.len() <= 1 || last.iter().all(|v| v.is_zero()) {
+            return Some(table)
+        }
+        let next = differences(last)?;
+        table.push(next);
+    }
+}
+
+/// Return the degree of the lowest-degree polynomial that generates the sequence, or None if the
+/// sequence is too short to tell (ie, it could be generated by a polynomial of degree `len - 1` or
+/// higher) or if its differences overflow. A sequence of zeros is treated as having degree 0.
+pub(crate) fn degree<T>(seq: &[T]) -> Option<usize> where T: CheckedSub + Zero + Clone {
+    let table = difference_table(seq)?;
+    let last = table.last().unwrap();
+    if !last.iter().all(|v| v.is_zero()) {
+        return None
+    }
+    // The row after the last non-zero row is all zeros.
+    Some(table.len().saturating_sub(2))
+}
+
+/// Return the value `k` steps after the end of the sequence, assuming that it is generated by the
+/// polynomial of lowest degree that fits it. Returns None if any step of the calculation
+/// overflows.
+pub(crate) fn extrapolate_forward<T>(seq: &[T], k: usize) -> Option<T>
+    where T: CheckedAdd + CheckedSub + Zero + Clone {
+    let mut lasts: Vec<T> = difference_table(seq)?.iter()
+        .filter_map(|row| row.last().cloned())
+        .collect();
+    // Extend every row of the table by one step, k times, working from the bottom row up. The
+    // bottom row is either all zeros or a single value, and is treated as constant.
+    for _ in 0..k {
+        for i in (0..lasts.len().saturating_sub(1)).rev() {
+            lasts[i] = lasts[i].checked_add(&lasts[i+1])?;
+        }
+    }
+    Some(lasts.first().cloned().unwrap_or_else(T::zero))
+}
+
+/// Return the value `k` steps before the start of the sequence, assuming that it is generated by
+/// the polynomial of lowest degree that fits it. Returns None if any step of the calculation
+/// overflows.
+pub(crate) fn extrapolate_backward<T>(seq: &[T], k: usize) -> Option<T>
+    where T: CheckedAdd + CheckedSub + Zero + Clone {
+    let reversed: Vec<T> = seq.iter().rev().cloned().collect();
+    extrapolate_forward(&reversed, k)
+}
+
+/// Multiply two polynomials, each given as coefficients in ascending order of power. Returns None
+/// if any coefficient overflows.
+fn multiply(a: &[Rational64], b: &[Rational64]) -> Option<Vec<Rational64>> {
+    if a.is_empty() || b.is_empty() {
+        return Some(vec!())
+    }
+    let mut product = vec!(Rational64::zero(); a.len() + b.len() - 1);
+    for (i, x) in a.iter().enumerate() {
+        for (j, y) in b.iter().enumerate() {
+            product[i+j] = product[i+j].checked_add(&x.checked_mul(y)?)?;
+        }
+    }
+    Some(product)
+}
+
+/// Add `scale` times polynomial `b` to polynomial `a` in place. Returns None if any coefficient
+/// overflows, leaving `a` partly updated.
+fn add_scaled(a: &mut Vec<Rational64>, b: &[Rational64], scale: Rational64) -> Option<()> {
+    if a.len() < b.len() {
+        a.resize(b.len(), Rational64::zero());
+    }
+    for (i, y) in b.iter().enumerate() {
+        a[i] = a[i].checked_add(&y.checked_mul(&scale)?)?;
+    }
+    Some(())
+}
+
+/// Remove trailing zero coefficients so that the length of the coefficient vector is one more
+/// than the polynomial's degree.
+fn trim(mut coeffs: Vec<Rational64>) -> Vec<Rational64> {
+    while coeffs.last().is_some_and(|c| c.is_zero()) {
+        coeffs.pop();
+    }
+    coeffs
+}
+
+/// Return the coefficients, in ascending order of power, of the lowest-degree polynomial p such
+/// that p(i) == seq[i] for every index i in the sequence. Uses Newton's forward difference
+/// formula. The zero polynomial is returned as an empty vector. Returns None if the sequence's
+/// differences or any coefficient overflow.
+pub(crate) fn interpolate(seq: &[i64]) -> Option<Vec<Rational64>> {
+    let table = difference_table(seq)?;
+    let mut coeffs: Vec<Rational64> = vec!();
+    // The falling factorial x(x - 1)...(x - k + 1) / k!, ie, x choose k.
+    let mut basis: Vec<Rational64> = vec!(Rational64::from_integer(1));
+    for (k, row) in table.iter().enumerate() {
+        if let Some(first) = row.first() {
+            add_scaled(&mut coeffs, &basis, Rational64::from_integer(*first))?;
+        }
+        let k = k as i64;
+        let next_factor = [Rational64::new(-k, k + 1), Rational64::new(1, k + 1)];
+        basis = multiply(&basis, &next_factor)?;
+    }
+    Some(trim(coeffs))
+}
+
+/// Return the coefficients, in ascending order of power, of the lowest-degree polynomial passing
+/// through each of the given (x, y) points. Uses Lagrange's formula, so the x values need not be
+/// evenly spaced, but they must be distinct. Returns an error if any coefficient overflows.
+pub(crate) fn interpolate_points(points: &[(i64, i64)]) -> Result<Vec<Rational64>, String> {
+    let overflowed = || String::from("Interpolation overflowed.");
+    let mut coeffs: Vec<Rational64> = vec!();
+    for (i, (xi, yi)) in points.iter().enumerate() {
+        let mut basis: Vec<Rational64> = vec!(Rational64::from_integer(1));
+        for (j, (xj, _)) in points.iter().enumerate() {
+            if i == j {
+                continue
+            }
+            if xi == xj {
+                return Err(format!("Points {i} and {j} share the x value {xi}."))
+            }
+            // Rational64::new negates a negative denominator, which overflows for i64::MIN.
+            let denom = i64::checked_sub(*xi, *xj)
+                .filter(|d| *d != i64::MIN)
+                .ok_or_else(overflowed)?;
+            let factor = [Rational64::new(xj.checked_neg().ok_or_else(overflowed)?, denom),
+                          Rational64::new(1, denom)];
+            basis = multiply(&basis, &factor).ok_or_else(overflowed)?;
+        }
+        add_scaled(&mut coeffs, &basis, Rational64::from_integer(*yi)).ok_or_else(overflowed)?;
+    }
+    Ok(trim(coeffs))
+}
+
+/// Evaluate a polynomial, given as coefficients in ascending order of power, at x. Returns None if
+/// the calculation overflows.
+pub(crate) fn evaluate(coeffs: &[Rational64], x: i64) -> Option<Rational64> {
+    let x = Rational64::from_integer(x);
+    coeffs.iter().rev().try_fold(Rational64::zero(), |acc, c| acc.checked_mul(&x)?.checked_add(c))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rationals(values: &[i64]) -> Vec<Rational64> {
+        values.iter().map(|v| Rational64::from_integer(*v)).collect()
+    }
+
+    #[test]
+    fn finds_degrees() {
+        let squares: Vec<i64> = (0..6).map(|x| x * x).collect();
+        assert_eq!(degree(&squares), Some(2));
+        assert_eq!(degree(&[3, 3, 3]), Some(0));
+        assert_eq!(degree(&[0, 0]), Some(0));
+        assert_eq!(degree(&[1, 2, 4]), None);
+        assert_eq!(degree(&[i64::MIN, i64::MAX]), None);
+        assert_eq!(difference_table(&[1, 4, 9]), Some(vec!(vec!(1, 4, 9), vec!(3, 5), vec!(2))));
+    }
+
+    #[test]
+    fn interpolates_polynomials() {
+        // 2x^2 - 3x + 1
+        let seq: Vec<i64> = (0..5).map(|x| 2 * x * x - 3 * x + 1).collect();
+        let coeffs = interpolate(&seq).unwrap();
+        assert_eq!(coeffs, rationals(&[1, -3, 2]));
+        assert_eq!(evaluate(&coeffs, 10), Some(Rational64::from_integer(171)));
+        assert_eq!(interpolate(&[0, 0, 0]), Some(vec!()));
+
+        // x^2 / 2 through unevenly spaced points.
+        let coeffs = interpolate_points(&[(-2, 2), (4, 8), (2, 2)]).unwrap();
+        assert_eq!(coeffs, vec!(Rational64::zero(), Rational64::zero(), Rational64::new(1, 2)));
+        assert!(interpolate_points(&[(1, 2), (1, 3)]).is_err());
+    }
+
+    #[test]
+    fn reports_overflow() {
+        // The differences fit, but the coefficient of x is 3 * (i64::MAX - 1) / 2.
+        assert_eq!(interpolate(&[0, i64::MAX - 1, i64::MAX - 1]), None);
+        assert_eq!(interpolate(&[i64::MIN, i64::MAX]), None);
+        assert!(interpolate_points(&[(i64::MIN, 0), (1, 0)]).is_err());
+        assert!(interpolate_points(&[(-1, 0), (i64::MAX, 0)]).is_err());
+        assert!(interpolate_points(&[(0, 0), (i64::MIN + 1, 0)]).is_ok());
+        assert_eq!(evaluate(&rationals(&[0, 0, 1]), i64::MAX), None);
+    }
+}
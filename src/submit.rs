@@ -0,0 +1,335 @@
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+use num::BigInt;
+use crate::inputs::{self, InputCache};
+use crate::options::Options;
+use crate::remote::Remote;
+
+/// The number of seconds to wait after a wrong answer if no `cooldown` option is given.
+const DEFAULT_COOLDOWN: u64 = 60;
+
+/// The website's judgement of a submitted answer.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Verdict {
+    Correct,
+    TooHigh,
+    TooLow,
+    /// Wrong, without saying which way.
+    Wrong,
+    /// Not judged because another answer was submitted too recently. Holds the number of seconds
+    /// left to wait, if the website said.
+    RateLimited(Option<u64>),
+    /// Not judged because the part is already solved or isn't unlocked yet.
+    WrongLevel
+}
+
+impl Verdict {
+
+    /// Read the verdict from the HTML page the website returns for a submission.
+    pub(crate) fn from_response(html: &str) -> Result<Verdict, String> {
+        if html.contains("That's the right answer") {
+            Ok(Verdict::Correct)
+        } else if html.contains("That's not the right answer") {
+            if html.contains("your answer is too high") {
+                Ok(Verdict::TooHigh)
+            } else if html.contains("your answer is too low") {
+                Ok(Verdict::TooLow)
+            } else {
+                Ok(Verdict::Wrong)
+            }
+        } else if html.contains("You gave an answer too recently") {
+            Ok(Verdict::RateLimited(parse_wait(html)))
+        } else if html.contains("You don't seem to be solving the right level") {
+            Ok(Verdict::WrongLevel)
+        } else {
+            Err(String::from("Could not find a verdict in the response."))
+        }
+    }
+
+    /// Whether the website actually judged the answer.
+    fn is_judgement(&self) -> bool {
+        !matches!(self, Verdict::RateLimited(_) | Verdict::WrongLevel)
+    }
+
+    /// The number of seconds to wait after this verdict before submitting again.
+    fn wait(&self, cooldown: u64) -> u64 {
+        match self {
+            Verdict::Correct | Verdict::WrongLevel => 0,
+            Verdict::RateLimited(Some(secs)) => *secs,
+            _ => cooldown
+        }
+    }
+}
+
+/// Read the time left from a message like `You have 1m 5s left to wait.`, in seconds.
+fn parse_wait(html: &str) -> Option<u64> {
+    let (before, _) = html.split_once(" left to wait")?;
+    let (_, time) = before.rsplit_once("You have ")?;
+    time.split_whitespace()
+        .map(|part| match part.split_at(part.len().checked_sub(1)?) {
+            (n, "m") => n.parse::<u64>().ok().map(|n| n * 60),
+            (n, "s") => n.parse::<u64>().ok(),
+            _ => None
+        })
+        .sum()
+}
+
+/// How a verdict is written in the history file.
+impl Display for Verdict {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Verdict::Correct => write!(f, "correct"),
+            Verdict::TooHigh => write!(f, "too-high"),
+            Verdict::TooLow => write!(f, "too-low"),
+            Verdict::Wrong => write!(f, "wrong"),
+            Verdict::RateLimited(None) => write!(f, "rate-limited"),
+            Verdict::RateLimited(Some(secs)) => write!(f, "rate-limited:{secs}"),
+            Verdict::WrongLevel => write!(f, "wrong-level")
+        }
+    }
+}
+
+impl FromStr for Verdict {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "correct" => Ok(Verdict::Correct),
+            "too-high" => Ok(Verdict::TooHigh),
+            "too-low" => Ok(Verdict::TooLow),
+            "wrong" => Ok(Verdict::Wrong),
+            "rate-limited" => Ok(Verdict::RateLimited(None)),
+            "wrong-level" => Ok(Verdict::WrongLevel),
+            other => match other.strip_prefix("rate-limited:") {
+                Some(secs) => secs.parse::<u64>()
+                    .map(|secs| Verdict::RateLimited(Some(secs)))
+                    .map_err(|e| format!("Could not parse wait in verdict `{other}`: {e}")),
+                None => Err(format!("Unknown verdict `{other}`."))
+            }
+        }
+    }
+}
+
+/// A submitted answer and its verdict, with the time it was submitted in seconds since the Unix
+/// epoch.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Submission {
+    time: u64,
+    day: usize,
+    part: usize,
+    answer: String,
+    verdict: Verdict
+}
+
+/// A history file line is the tab-separated time, day, part, verdict and answer.
+impl FromStr for Submission {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = s.splitn(5, '\t').collect();
+        let [time, day, part, verdict, answer] = fields[..] else {
+            return Err(format!("Expected five tab-separated fields in `{s}`."))
+        };
+        let parse = |field: &str, name: &str| field.parse::<u64>()
+            .map_err(|e| format!("Could not parse {name} from `{s}`: {e}"));
+        Ok(Submission {
+            time: parse(time, "time")?,
+            day: parse(day, "day")? as usize,
+            part: parse(part, "part")? as usize,
+            answer: String::from(answer),
+            verdict: Verdict::from_str(verdict)?
+        })
+    }
+}
+
+impl Display for Submission {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}\t{}\t{}\t{}\t{}", self.time, self.day, self.part, self.verdict, self.answer)
+    }
+}
+
+/// Every answer submitted for a year's puzzles, kept in a file so that we never resubmit an answer
+/// whose verdict we already know.
+#[derive(Clone, Debug)]
+pub(crate) struct History {
+    path: PathBuf,
+    /// The number of seconds to wait after a wrong answer.
+    cooldown: u64,
+    submissions: Vec<Submission>
+}
+
+impl History {
+
+    /// Load the history from `path`, which need not exist yet.
+    pub(crate) fn load(path: &Path, cooldown: u64) -> Result<History, String> {
+        let submissions = match fs::read_to_string(path) {
+            Ok(s) => s.lines().map(Submission::from_str).collect::<Result<Vec<_>, _>>()?,
+            Err(_) if !path.exists() => vec!(),
+            Err(e) => return Err(format!("Could not read {}: {e}", path.display()))
+        };
+        Ok(History { path: path.to_path_buf(), cooldown, submissions })
+    }
+
+    /// Load the history from the file given by the `history` option, or by default from
+    /// `submissions.tsv` alongside the year's cached inputs, using the `cooldown` option.
+    pub(crate) fn from_options(opts: &Options) -> Result<History, String> {
+        let path = match opts.get("history") {
+            Some(path) => PathBuf::from(path),
+            None => InputCache::from_options(opts)
+                .year_dir(inputs::year(opts))
+                .join("submissions.tsv")
+        };
+        let cooldown = opts.get("cooldown").map_or(Ok(DEFAULT_COOLDOWN), |c| c.parse::<u64>()
+            .map_err(|e| format!("Could not parse cooldown: {e}")))?;
+        History::load(&path, cooldown)
+    }
+
+    fn for_part(&self, day: usize, part: usize) -> impl Iterator<Item = &Submission> {
+        self.submissions.iter().filter(move |s| s.day == day && s.part == part)
+    }
+
+    /// Return an error explaining why `answer` shouldn't be submitted at time `now`, if it
+    /// shouldn't: because it is empty or contains whitespace, because the part is already solved,
+    /// because the answer was already judged, because a known too-high or too-low answer rules it
+    /// out, or because we are still waiting out a cooldown.
+    pub(crate) fn check(&self, day: usize, part: usize, answer: &str, now: u64)
+        -> Result<(), String> {
+        // Answers are single words, and the history file couldn't hold one spanning lines.
+        if answer.is_empty() || answer.contains(char::is_whitespace) {
+            return Err(format!("Answer {answer:?} is empty or contains whitespace."))
+        }
+        if let Some(s) = self.for_part(day, part).find(|s| s.verdict == Verdict::Correct) {
+            return Err(format!("Already solved with answer {}.", s.answer))
+        }
+        if let Some(s) = self.for_part(day, part)
+            .find(|s| s.answer == answer && s.verdict.is_judgement()) {
+            return Err(format!("Answer {answer} was already submitted and was {}.", s.verdict))
+        }
+        if let Ok(value) = answer.parse::<BigInt>() {
+            for s in self.for_part(day, part) {
+                let Ok(known) = s.answer.parse::<BigInt>() else {
+                    continue
+                };
+                match s.verdict {
+                    Verdict::TooHigh if value >= known => return Err(format!(
+                        "Answer {answer} is not below {known}, which was too high."
+                    )),
+                    Verdict::TooLow if value <= known => return Err(format!(
+                        "Answer {answer} is not above {known}, which was too low."
+                    )),
+                    _ => {}
+                }
+            }
+        }
+        if let Some(last) = self.for_part(day, part).last() {
+            let ready = last.time + last.verdict.wait(self.cooldown);
+            if now < ready {
+                return Err(format!("Wait {}s before submitting again.", ready - now))
+            }
+        }
+        Ok(())
+    }
+
+    /// Add a submission to the history and append it to the history file.
+    fn record(&mut self, submission: Submission) -> Result<(), String> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Could not create {}: {e}", parent.display()))?;
+        }
+        fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut f| writeln!(f, "{submission}"))
+            .map_err(|e| format!("Could not write to {}: {e}", self.path.display()))?;
+        self.submissions.push(submission);
+        Ok(())
+    }
+
+    /// Check an answer against the history and, if it passes, submit it to the website and record
+    /// the verdict.
+    pub(crate) fn submit(&mut self, remote: &Remote, year: u32, day: usize, part: usize,
+                         answer: &str, now: u64) -> Result<Verdict, String> {
+        self.check(day, part, answer, now)?;
+        let response = remote.post_form(
+            &format!("/{year}/day/{day}/answer"),
+            &[("level", &part.to_string()), ("answer", answer)]
+        )?;
+        let verdict = Verdict::from_response(&response)?;
+        self.record(Submission { time: now, day, part, answer: String::from(answer), verdict })?;
+        Ok(verdict)
+    }
+}
+
+/// The current time in seconds since the Unix epoch.
+pub(crate) fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::remote::stub;
+    use super::*;
+
+    fn history(lines: &[&str]) -> History {
+        History {
+            path: PathBuf::new(),
+            cooldown: DEFAULT_COOLDOWN,
+            submissions: lines.iter().map(|l| Submission::from_str(l).unwrap()).collect()
+        }
+    }
+
+    #[test]
+    fn reads_verdicts_from_responses() {
+        let wrong = "<p>That's not the right answer; your answer is too low. Please wait</p>";
+        assert_eq!(Verdict::from_response(wrong), Ok(Verdict::TooLow));
+        let limited = "<p>You gave an answer too recently. You have 1m 5s left to wait.</p>";
+        assert_eq!(Verdict::from_response(limited), Ok(Verdict::RateLimited(Some(65))));
+        assert!(Verdict::from_response("<p>Hello</p>").is_err());
+        for v in [Verdict::TooHigh, Verdict::RateLimited(Some(30)), Verdict::RateLimited(None)] {
+            assert_eq!(Verdict::from_str(&v.to_string()), Ok(v));
+        }
+    }
+
+    #[test]
+    fn refuses_answers_ruled_out_by_history() {
+        let h = history(&[
+            "1000\t5\t1\ttoo-high\t500",
+            "1100\t5\t1\ttoo-low\t100",
+            "1200\t5\t1\twrong\t300",
+            "1000\t6\t1\tcorrect\t42"
+        ]);
+        assert!(h.check(5, 1, "500", 2000).is_err());
+        assert!(h.check(5, 1, "600", 2000).is_err());
+        assert!(h.check(5, 1, "100", 2000).is_err());
+        assert!(h.check(5, 1, "300", 2000).is_err());
+        assert!(h.check(5, 1, "250", 1230).is_err());
+        assert_eq!(h.check(5, 1, "250", 1260), Ok(()));
+        assert_eq!(h.check(5, 2, "500", 1230), Ok(()));
+        assert!(h.check(6, 1, "43", 2000).is_err());
+        assert!(h.check(5, 2, "", 2000).is_err());
+        assert!(h.check(5, 2, "Galaxy 1: 3\nGalaxy 2: 4", 2000).is_err());
+        assert!(h.check(5, 2, "a\tb", 2000).is_err());
+    }
+
+    #[test]
+    fn submits_to_the_website_and_records_the_verdict() {
+        let (url, server) = stub::serve_once("<article><p>That's the right answer!</p></article>");
+        let path = std::env::temp_dir().join(format!("aoc23-history-{}.tsv", std::process::id()));
+        let mut h = History::load(&path, DEFAULT_COOLDOWN).unwrap();
+
+        let verdict = h.submit(&Remote::new(&url, "secret"), 2023, 5, 2, "46", 1000);
+        let (head, body) = server.join().unwrap();
+
+        assert_eq!(verdict, Ok(Verdict::Correct));
+        assert!(head.starts_with("POST /2023/day/5/answer "));
+        assert_eq!(body, "level=2&answer=46");
+        assert_eq!(History::load(&path, DEFAULT_COOLDOWN).unwrap().submissions, h.submissions);
+        assert!(h.check(5, 2, "47", 2000).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+}
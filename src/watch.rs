@@ -0,0 +1,185 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+/// Detects changes to a set of files by polling their modification times.
+#[derive(Clone, Debug)]
+pub(crate) struct Watcher {
+    files: Vec<PathBuf>,
+    /// The modification time of each file when last checked, or None if it didn't exist. Starts
+    /// out empty so that the first check reports a change.
+    stamps: Vec<Option<SystemTime>>
+}
+
+impl Watcher {
+
+    pub(crate) fn new(files: Vec<PathBuf>) -> Watcher {
+        Watcher { files, stamps: vec!() }
+    }
+
+    /// Whether any file has been modified, created or deleted since the last check.
+    pub(crate) fn changed(&mut self) -> bool {
+        let stamps: Vec<Option<SystemTime>> = self.files.iter()
+            .map(|f| fs::metadata(f).and_then(|m| m.modified()).ok())
+            .collect();
+        let changed = stamps != self.stamps;
+        self.stamps = stamps;
+        changed
+    }
+}
+
+/// Read the answer and time taken from the runner's output.
+fn parse_run_output(stdout: &str) -> Option<(String, String)> {
+    let (_, rest) = stdout.split_once(" answer:\n")?;
+    let (answer, time) = rest.rsplit_once("\nTime taken: ")?;
+    Some((String::from(answer), String::from(time.trim())))
+}
+
+/// Describe how an answer differs from the previous one: the change in value for a number, or the
+/// changed lines for anything else.
+fn describe_change(previous: Option<&str>, answer: &str) -> String {
+    let Some(previous) = previous else {
+        return String::from("first run")
+    };
+    if previous == answer {
+        return String::from("unchanged")
+    }
+    if let (Ok(old), Ok(new)) = (previous.parse::<i128>(), answer.parse::<i128>()) {
+        return match new.checked_sub(old) {
+            Some(delta) => format!("was {old}, change {delta:+}"),
+            None => format!("was {old}")
+        }
+    }
+    let old: Vec<&str> = previous.lines().collect();
+    let new: Vec<&str> = answer.lines().collect();
+    let mut diff: Vec<String> = vec!(String::from("changed:"));
+    for i in 0..old.len().max(new.len()) {
+        match (old.get(i), new.get(i)) {
+            (Some(o), Some(n)) if o == n => {},
+            (o, n) => {
+                diff.extend(o.map(|o| format!("- {o}")));
+                diff.extend(n.map(|n| format!("+ {n}")));
+            }
+        }
+    }
+    diff.join("\n")
+}
+
+/// Run cargo for the same profile as the executable `exe`, so that rebuilding replaces it.
+fn cargo(exe: &Path, args: &[&str]) -> std::io::Result<Output> {
+    let mut command = Command::new("cargo");
+    command.args(args);
+    if exe.components().any(|c| c.as_os_str() == "release") {
+        command.arg("--release");
+    }
+    command.output()
+}
+
+/// Rebuild the runner `exe`, run the day's example tests, then run each part on the real input,
+/// printing each answer along with its timing and how it differs from `previous`, which is
+/// updated.
+fn rebuild_and_run(exe: &Path, module: &str, run_args: &[Vec<String>],
+                   previous: &mut [Option<String>]) {
+    match cargo(exe, &["build", "--quiet"]) {
+        Ok(output) if output.status.success() => {},
+        Ok(output) => {
+            println!("Build failed:\n{}", String::from_utf8_lossy(&output.stderr));
+            return
+        },
+        Err(e) => {
+            println!("Could not run cargo: {e}");
+            return
+        }
+    }
+
+    match cargo(exe, &["test", "--quiet", &format!("{module}::")]) {
+        Ok(output) => {
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            if output.status.success() {
+                match stdout.lines().find(|l| l.starts_with("test result")) {
+                    Some(summary) if !summary.contains(" 0 passed") => {
+                        println!("Examples: {summary}")
+                    },
+                    _ => println!("Examples: none")
+                }
+            } else {
+                println!("Examples failed:\n{stdout}");
+            }
+        },
+        Err(e) => println!("Could not run cargo: {e}")
+    }
+
+    for (args, previous) in run_args.iter().zip(previous.iter_mut()) {
+        let label = format!("Part {}", args[1]);
+        let output = match Command::new(exe).args(args).output() {
+            Ok(output) => output,
+            Err(e) => {
+                println!("{label}: could not run: {e}");
+                continue
+            }
+        };
+        match parse_run_output(&String::from_utf8_lossy(&output.stdout)) {
+            Some((answer, time)) => {
+                println!("{label}: {answer} in {time} ({})",
+                         describe_change(previous.as_deref(), &answer));
+                *previous = Some(answer);
+            },
+            None => println!("{label} failed:\n{}", String::from_utf8_lossy(&output.stderr))
+        }
+    }
+}
+
+/// Rerun a day whenever any of `files` changes, polling every `interval`. Each entry of `run_args`
+/// holds the arguments for one run of the runner. Never returns.
+pub(crate) fn watch(day: usize, files: Vec<PathBuf>, run_args: Vec<Vec<String>>,
+                    interval: Duration) -> ! {
+    // Find the executable before rebuilding, as afterwards it may report the replaced file.
+    let exe = env::current_exe().expect("Could not find the runner's executable.");
+    let module = format!("day_{day:02}");
+    let mut watcher = Watcher::new(files);
+    let mut previous: Vec<Option<String>> = vec!(None; run_args.len());
+    loop {
+        if watcher.changed() {
+            println!("=== Day {day} ===");
+            rebuild_and_run(&exe, &module, &run_args, &mut previous);
+        }
+        thread::sleep(interval);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_runner_output() {
+        let stdout = "Day 11, part 1 answer:\nGalaxy 1: 3\nGalaxy 2: 4\nTime taken: 1.5ms\n";
+        let (answer, time) = parse_run_output(stdout).unwrap();
+        assert_eq!(answer, "Galaxy 1: 3\nGalaxy 2: 4");
+        assert_eq!(time, "1.5ms");
+    }
+
+    #[test]
+    fn describes_changes() {
+        assert_eq!(describe_change(None, "5"), "first run");
+        assert_eq!(describe_change(Some("5"), "5"), "unchanged");
+        assert_eq!(describe_change(Some("5"), "3"), "was 5, change -2");
+        assert_eq!(describe_change(Some("a\nb"), "a\nc\nd"), "changed:\n- b\n+ c\n+ d");
+    }
+
+    #[test]
+    fn notices_modified_files() {
+        let path = env::temp_dir().join(format!("aoc23-watch-{}.txt", std::process::id()));
+        let mut watcher = Watcher::new(vec!(path.clone()));
+        assert!(watcher.changed());
+        assert!(!watcher.changed());
+        fs::write(&path, "x").unwrap();
+        assert!(watcher.changed());
+        assert!(!watcher.changed());
+        fs::remove_file(&path).unwrap();
+        assert!(watcher.changed());
+    }
+}